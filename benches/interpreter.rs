@@ -0,0 +1,59 @@
+//! How fast programs run, compiled and a statement at a time. Run with
+//! `cargo bench --bench interpreter`.
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use zx_spectrum::console::Capture;
+use zx_spectrum::screen;
+use zx_spectrum::{parse_file, Interpreter, Status};
+
+const LOOP: &str = "\
+10 LET t=0
+20 FOR i=1 TO 10000
+30 LET t=t+i*2-1
+40 NEXT i";
+
+const STRINGS: &str = "\
+10 FOR i=1 TO 1000
+20 PRINT \"The quick brown fox \";i,\"jumps over the lazy dog\";
+30 PRINT \"...\",\"and again\"
+40 NEXT i";
+
+const RECURSION: &str = "\
+10 LET d=0
+20 GO SUB 100
+30 STOP
+100 LET d=d+1
+110 IF d<2000 THEN GO SUB 100
+120 RETURN";
+
+/// Runs a program to the end, compiled or not, with its output captured.
+fn run(program: &str, compiled: bool) -> Status {
+    let mut interpreter = Interpreter::new(parse_file(program, true).unwrap());
+    interpreter.set_console(Box::new(Capture::default()));
+    match compiled {
+        true => interpreter.run(),
+        false => interpreter.run_until(|_| false),
+    }
+}
+
+fn programs(c: &mut Criterion) {
+    for (name, program) in [("loop", LOOP), ("strings", STRINGS), ("recursion", RECURSION)] {
+        let mut group = c.benchmark_group(name);
+        group.bench_function("compiled", |b| b.iter(|| run(black_box(program), true)));
+        group.bench_function("interpreted", |b| b.iter(|| run(black_box(program), false)));
+        group.finish();
+    }
+}
+
+fn graphics(c: &mut Criterion) {
+    // A screen full of different bytes and colours, so nothing renders the same way twice
+    let mut memory = vec![0; 0x10000];
+    for (i, byte) in memory[0x4000..0x5B00].iter_mut().enumerate() {
+        *byte = (i * 37 % 256) as u8;
+    }
+    c.bench_function("screen::render", |b| b.iter(|| screen::render(black_box(&memory))));
+}
+
+criterion_group!(benches, programs, graphics);
+criterion_main!(benches);
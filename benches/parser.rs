@@ -0,0 +1,44 @@
+//! How fast listings and expressions parse. Run with `cargo bench --bench parser`.
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use zx_spectrum::{parse_file, Expr};
+
+/// A listing of `lines` lines, using every kind of statement a big program would.
+fn listing(lines: usize) -> String {
+    let statements = [
+        "LET total=total+i*3-(i/2)",
+        "PRINT \"Line \";i,total;",
+        "IF total>1000 THEN LET total=0: GO SUB 9000",
+        "FOR j=1 TO 10 STEP 2",
+        "NEXT j",
+        "REM a comment about nothing in particular",
+        "INPUT \"How many?\",count",
+        "GO TO 10",
+    ];
+    (0..lines)
+        .map(|i| format!("{} {}\n", (i + 1) * 10, statements[i % statements.len()]))
+        .collect()
+}
+
+fn parse_listings(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_file");
+    for lines in [100, 1000, 10000] {
+        let listing = listing(lines);
+        group.throughput(Throughput::Bytes(listing.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(lines), &listing, |b, listing| {
+            b.iter(|| parse_file(black_box(listing), true).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn parse_expressions(c: &mut Criterion) {
+    let long = (1..=200).map(|i| format!("a{}*{}", i % 7, i)).collect::<Vec<_>>().join("+");
+    let nested = format!("{}1{}", "(".repeat(100), "+1)".repeat(100));
+    c.bench_function("Expr::parse/long", |b| b.iter(|| Expr::parse(black_box(&long)).unwrap()));
+    c.bench_function("Expr::parse/nested", |b| b.iter(|| Expr::parse(black_box(&nested)).unwrap()));
+}
+
+criterion_group!(benches, parse_listings, parse_expressions);
+criterion_main!(benches);
@@ -0,0 +1,313 @@
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+
+use crate::parser::{find_line, Expr, Ident, InputItem, Instr, Line, Symbols, TapeCommand, TapeData};
+
+/// A lint warning, reported against the BASIC line number it was found on.
+#[derive(Debug, PartialEq)]
+pub struct Warning {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{}: {}", self.line, self.message)
+    }
+}
+
+/// Statically checks a parsed program, without running it.
+pub fn check(lines: &[Line]) -> Vec<Warning> {
+    // Variables are compared by their symbols, so `A` and `a` are the same one
+    let mut lines = lines.to_vec();
+    let mut symbols = Symbols::default();
+    lines.iter_mut().for_each(|line| line.instr.intern(&mut symbols));
+    let lines = &lines[..];
+    let mut warnings = vec![];
+    duplicate_lines(lines, &mut warnings);
+    missing_targets(lines, &mut warnings);
+    unassigned_vars(lines, &mut warnings);
+    unmatched_next(lines, &mut warnings);
+    unreachable_lines(lines, &mut warnings);
+    warnings.sort_by_key(|w| lines.iter().position(|l| l.number == w.line));
+    warnings
+}
+
+/// Calls `f` on every statement of `instr`, including those inside IF ... THEN.
+fn for_each_statement<'a, 'b>(instr: &'b Instr<'a>, f: &mut impl FnMut(&'b Instr<'a>)) {
+    f(instr);
+    match instr {
+        Instr::Multi(instrs) => instrs.iter().for_each(|i| for_each_statement(i, f)),
+        Instr::IfThen(_, instr) => for_each_statement(instr, f),
+        _ => {}
+    }
+}
+
+fn idents<'a, 'b>(expr: &'b Expr<'a>, out: &mut Vec<&'b Ident<'a>>) {
+    match expr {
+        Expr::Ident(ident) => out.push(ident),
+        Expr::Int(_) | Expr::String(_) | Expr::Err | Expr::Erl => {}
+        Expr::Add(a, b)
+        | Expr::Sub(a, b)
+        | Expr::Mul(a, b)
+        | Expr::Div(a, b)
+        | Expr::Gt(a, b)
+        | Expr::Lt(a, b)
+        | Expr::Eq(a, b)
+        | Expr::Ge(a, b)
+        | Expr::Le(a, b)
+        | Expr::Ne(a, b) => {
+            idents(a, out);
+            idents(b, out);
+        }
+    }
+}
+
+fn duplicate_lines(lines: &[Line], warnings: &mut Vec<Warning>) {
+    let mut seen = HashSet::new();
+    for line in lines {
+        if !seen.insert(line.number) {
+            warnings.push(Warning {
+                line: line.number,
+                message: format!("line {} is defined more than once", line.number),
+            });
+        }
+    }
+}
+
+fn missing_targets(lines: &[Line], warnings: &mut Vec<Warning>) {
+    let numbers: HashSet<_> = lines.iter().map(|l| l.number).collect();
+    for line in lines {
+        for_each_statement(&line.instr, &mut |instr| {
+            let (keyword, target) = match instr {
+                Instr::Goto(target) => ("GO TO", target),
+                Instr::Gosub(target) => ("GO SUB", target),
+                Instr::OnErrorGoto(target) if *target != 0 => ("ON ERROR GO TO", target),
+                _ => return,
+            };
+            if !numbers.contains(target) {
+                warnings.push(Warning {
+                    line: line.number,
+                    message: format!("{} {}: there is no line {}", keyword, target, target),
+                });
+            }
+        });
+    }
+}
+
+fn unassigned_vars(lines: &[Line], warnings: &mut Vec<Warning>) {
+    let mut assigned = HashSet::new();
+    let mut reads = vec![];
+    for line in lines {
+        for_each_statement(&line.instr, &mut |instr| {
+            let mut read = vec![];
+            match instr {
+                Instr::Print(first, rest, _) => {
+                    first.iter().for_each(|e| idents(e, &mut read));
+                    rest.iter().for_each(|(_, e)| idents(e, &mut read));
+                }
+                Instr::Assign(Expr::Ident(var), expr) => {
+                    assigned.insert(var.symbol());
+                    idents(expr, &mut read);
+                }
+                Instr::Input(items) => {
+                    for item in items {
+                        match item {
+                            InputItem::Var(var) | InputItem::Line(var) => {
+                                assigned.insert(var.symbol());
+                            }
+                            InputItem::Prompt(expr) => idents(expr, &mut read),
+                            InputItem::At(line, column) => {
+                                idents(line, &mut read);
+                                idents(column, &mut read);
+                            }
+                            InputItem::Separator(_) => {}
+                        }
+                    }
+                }
+                Instr::For(Expr::Ident(var), start, end, step) => {
+                    assigned.insert(var.symbol());
+                    [start, end, step].iter().for_each(|e| idents(e, &mut read));
+                }
+                Instr::IfThen(cond, _) => idents(cond, &mut read),
+                Instr::Tape(_, name, data) => {
+                    idents(name, &mut read);
+                    if let TapeData::Code(start, length) = data {
+                        start.iter().chain(length).for_each(|e| idents(e, &mut read));
+                    }
+                }
+                _ => {}
+            }
+            reads.extend(read.into_iter().map(|var| (line.number, var)));
+        });
+    }
+
+    let mut reported = HashSet::new();
+    for (line, var) in reads {
+        if !assigned.contains(&var.symbol()) && reported.insert((line, var.symbol())) {
+            warnings.push(Warning {
+                line,
+                message: format!("variable {} is read but never assigned", var),
+            });
+        }
+    }
+}
+
+fn unmatched_next(lines: &[Line], warnings: &mut Vec<Warning>) {
+    let mut open_loops = vec![];
+    for line in lines {
+        for_each_statement(&line.instr, &mut |instr| match instr {
+            Instr::For(Expr::Ident(var), ..) => {
+                open_loops.retain(|v| *v != var.symbol());
+                open_loops.push(var.symbol());
+            }
+            Instr::Next(Expr::Ident(var)) => match open_loops.iter().rposition(|v| *v == var.symbol()) {
+                Some(i) => open_loops.truncate(i),
+                None => warnings.push(Warning {
+                    line: line.number,
+                    message: format!("NEXT {} without a matching FOR", var),
+                }),
+            },
+            _ => {}
+        });
+    }
+}
+
+/// Where control can go after running `instr`: the lines it may jump to, and
+/// whether it can fall through to the next statement.
+fn successors(lines: &[Line], instr: &Instr, targets: &mut Vec<usize>) -> bool {
+    match instr {
+        Instr::Goto(number) => {
+            targets.push(find_line(lines, *number));
+            false
+        }
+        Instr::Gosub(number) => {
+            targets.push(find_line(lines, *number));
+            true
+        }
+        Instr::Run(number) => {
+            targets.push(find_line(lines, number.unwrap_or(0)));
+            false
+        }
+        // Where an error is handled from is reached some other way
+        Instr::OnErrorGoto(number) if *number != 0 => {
+            targets.push(find_line(lines, *number));
+            true
+        }
+        Instr::Return | Instr::Stop | Instr::Continue | Instr::Resume(_) => false,
+        // Loading a program replaces this one
+        Instr::Tape(TapeCommand::Load | TapeCommand::Merge, _, TapeData::Program(_)) => false,
+        Instr::IfThen(_, instr) => {
+            successors(lines, instr, targets);
+            true
+        }
+        // A false IF skips the rest of its line, so from there on it can fall through
+        Instr::Multi(instrs) => {
+            let mut conditional = false;
+            for instr in instrs {
+                if !successors(lines, instr, targets) {
+                    return conditional;
+                }
+                conditional |= matches!(instr, Instr::IfThen(..));
+            }
+            true
+        }
+        _ => true,
+    }
+}
+
+fn unreachable_lines(lines: &[Line], warnings: &mut Vec<Warning>) {
+    let mut reached = HashSet::new();
+    let mut todo = vec![0];
+    while let Some(i) = todo.pop() {
+        if i >= lines.len() || !reached.insert(i) {
+            continue;
+        }
+        let mut targets = vec![];
+        if successors(lines, &lines[i].instr, &mut targets) {
+            targets.push(i + 1);
+        }
+        todo.extend(targets);
+    }
+    for (i, line) in lines.iter().enumerate() {
+        if !reached.contains(&i) {
+            warnings.push(Warning {
+                line: line.number,
+                message: "line is unreachable".to_string(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check;
+    use crate::parser::parse_file;
+
+    fn messages(file: &str) -> Vec<String> {
+        let lines = parse_file(file, true).expect("Failed to parse");
+        check(&lines).iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn test_clean_program() {
+        assert!(messages("10 FOR i = 1 TO 3\n20 PRINT i\n30 NEXT i").is_empty());
+    }
+
+    #[test]
+    fn test_missing_target() {
+        assert_eq!(
+            messages("10 GO SUB 100\n20 GO TO 10"),
+            vec!["10: GO SUB 100: there is no line 100"]
+        );
+    }
+
+    #[test]
+    fn test_unassigned_var() {
+        assert_eq!(
+            messages("10 LET a = b + 1\n20 PRINT a; b"),
+            vec![
+                "10: variable b is read but never assigned",
+                "20: variable b is read but never assigned"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unmatched_next() {
+        assert_eq!(
+            messages("10 FOR i = 1 TO 2\n20 NEXT i\n30 NEXT i"),
+            vec!["30: NEXT i without a matching FOR"]
+        );
+    }
+
+    #[test]
+    fn test_unreachable() {
+        assert_eq!(
+            messages("10 GO TO 30\n20 PRINT 1\n30 PRINT 2: STOP\n40 PRINT 3"),
+            vec!["20: line is unreachable", "40: line is unreachable"]
+        );
+        assert!(messages("10 IF 1 THEN GO TO 30\n20 PRINT 1\n30 PRINT 2").is_empty());
+        let program = "10 LET a=2\n20 IF a=1 THEN PRINT 1: GO TO 40\n30 PRINT 2\n40 STOP";
+        assert!(messages(program).is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_lines() {
+        assert_eq!(
+            messages("10 PRINT 1\n10 PRINT 2"),
+            vec!["10: line 10 is defined more than once"]
+        );
+    }
+
+    #[test]
+    fn test_error_handler() {
+        // The handler is reached by trapping an error, and ERR and ERL aren't variables
+        let program = "10 ON ERROR GO TO 100\n20 PRINT 1/0\n30 STOP\n100 PRINT ERR;ERL\n110 RESUME NEXT";
+        assert!(messages(program).is_empty());
+        assert_eq!(
+            messages("10 ON ERROR GO TO 100\n20 PRINT ERR"),
+            vec!["10: ON ERROR GO TO 100: there is no line 100"]
+        );
+    }
+}
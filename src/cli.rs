@@ -1,8 +1,223 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+use std::time::Duration;
 
+use zx_spectrum::{Limits, Snapshot, TraceFormat};
+
+/// An interpreter for ZX Spectrum BASIC
 #[derive(Parser, Debug)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub(crate) struct Args {
-    pub path: std::path::PathBuf,
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+    #[clap(flatten)]
+    pub run: RunArgs,
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum Command {
+    /// Run a program (the default when no subcommand is given)
+    Run(RunArgs),
+    /// Parse a program without running it, and report lint warnings
+    Check(SourceArgs),
+    /// Renumber a program's lines, and print it as a numbered listing
+    Renumber(RenumberArgs),
+    /// Reformat a program as a canonical listing
+    Fmt(FmtArgs),
+    /// Start an interactive session, optionally with a program loaded
+    Repl(ReplArgs),
+    /// Save a program to a .tap tape image
+    Tap(TapArgs),
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct SourceArgs {
+    /// A BASIC listing, a .tap tape image, or a .sna or .z80 snapshot
+    #[clap(required = true)]
+    pub path: Option<PathBuf>,
+    #[clap(long, short, action)]
+    pub prefixed: bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct RunArgs {
+    #[clap(flatten)]
+    pub source: SourceArgs,
+    /// Run the program even if some lines fail to parse
+    #[clap(long, short, action)]
+    pub recover: bool,
+    /// Directory or .tap image for SAVE, LOAD, VERIFY and MERGE
+    #[clap(long)]
+    pub tape: Option<PathBuf>,
+    /// Allow statements the Spectrum doesn't have: ON ERROR GO TO, RESUME, and ERR and ERL
+    #[clap(long, action)]
+    pub extended: bool,
+    /// Run under the debugger, stopping before the first statement
+    #[clap(long, action)]
+    pub debug: bool,
+    /// Log each statement run, and each variable set, to stderr
+    #[clap(long, action)]
+    pub trace: bool,
+    /// Write the trace to a file instead of stderr
+    #[clap(long, value_name = "PATH", requires = "trace")]
+    pub trace_file: Option<PathBuf>,
+    /// How to write the trace: human or json (one object a line)
+    #[clap(long, value_name = "FORMAT", value_parser = parse_trace_format, default_value = "human")]
+    pub trace_format: TraceFormat,
+    /// Time each line and kind of statement, and report where the time went to stderr
+    #[clap(long, action)]
+    pub profile: bool,
+    /// Also write the time under each GO SUB as folded stacks, for flame graph tools
+    #[clap(long, value_name = "PATH", requires = "profile")]
+    pub profile_folded: Option<PathBuf>,
+    #[clap(flatten)]
+    pub limits: LimitArgs,
+}
+
+fn parse_trace_format(arg: &str) -> Result<TraceFormat, String> {
+    match arg.to_ascii_lowercase().as_str() {
+        "human" => Ok(TraceFormat::Human),
+        "json" => Ok(TraceFormat::Json),
+        _ => Err("expected human or json".to_string()),
+    }
+}
+
+// Limits for running programs that can't be trusted. A program that goes over one stops
+// with "X Limit exceeded". A doc comment here would replace the tool's own description.
+#[derive(clap::Args, Debug)]
+#[clap(next_help_heading = "Limits")]
+pub(crate) struct LimitArgs {
+    /// Stop after running this many statements
+    #[clap(long, value_name = "COUNT")]
+    pub max_statements: Option<u64>,
+    /// Stop after running for this many seconds
+    #[clap(long, value_name = "SECONDS", value_parser = parse_seconds)]
+    pub timeout: Option<Duration>,
+    /// Stop when defining more than this many variables
+    #[clap(long, value_name = "COUNT")]
+    pub max_variables: Option<usize>,
+    /// Stop when the variables take up more than this many bytes
+    #[clap(long, value_name = "BYTES")]
+    pub max_memory: Option<usize>,
+    /// Stop when printing more than this many bytes
+    #[clap(long, value_name = "BYTES")]
+    pub max_output: Option<usize>,
+}
+
+impl LimitArgs {
+    pub fn limits(&self) -> Limits {
+        Limits {
+            statements: self.max_statements,
+            timeout: self.timeout,
+            variables: self.max_variables,
+            memory: self.max_memory,
+            output: self.max_output,
+        }
+    }
+}
+
+fn parse_seconds(arg: &str) -> Result<Duration, String> {
+    let seconds: f64 = arg.parse().map_err(|e| format!("{}", e))?;
+    Duration::try_from_secs_f64(seconds).map_err(|e| format!("{}", e))
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct RenumberArgs {
+    #[clap(flatten)]
+    pub source: SourceArgs,
+    /// Number of the first line
+    #[clap(long, default_value_t = 10)]
+    pub start: usize,
+    /// Difference between consecutive line numbers
+    #[clap(long, default_value_t = 10)]
+    pub step: usize,
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct FmtArgs {
+    #[clap(flatten)]
+    pub source: SourceArgs,
+    /// Overwrite the file instead of printing the listing
+    #[clap(long, short, action)]
+    pub write: bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct ReplArgs {
+    pub path: Option<PathBuf>,
     #[clap(long, short, action)]
     pub prefixed: bool,
-}
\ No newline at end of file
+    /// Directory or .tap image for SAVE, LOAD, VERIFY and MERGE
+    #[clap(long)]
+    pub tape: Option<PathBuf>,
+    /// Allow statements the Spectrum doesn't have: ON ERROR GO TO, RESUME, and ERR and ERL
+    #[clap(long, action)]
+    pub extended: bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct TapArgs {
+    #[clap(flatten)]
+    pub source: SourceArgs,
+    /// Where to write the tape image
+    #[clap(long, short)]
+    pub output: PathBuf,
+    /// Name of the program on tape, at most 10 characters (defaults to the file name)
+    #[clap(long)]
+    pub name: Option<String>,
+    /// Line to run from when the program is loaded
+    #[clap(long)]
+    pub autostart: Option<usize>,
+}
+
+/// A program read from disk. Tape images and snapshots also carry where to start, the saved
+/// variables and, for snapshots, the rest of memory.
+#[derive(Debug, Default)]
+pub(crate) struct Source {
+    pub text: String,
+    pub prefixed: bool,
+    pub autostart: Option<usize>,
+    pub variables: Vec<(String, i64)>,
+    pub memory: Option<Box<[u8]>>,
+    pub cont: Option<(usize, usize)>, // (line, statement) for CONTINUE to pick up at
+}
+
+impl SourceArgs {
+    pub fn is_tape(&self) -> bool {
+        let path = self.path.as_ref().expect("clap requires a path");
+        path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("tap"))
+    }
+
+    pub fn read(&self) -> anyhow::Result<Source> {
+        use anyhow::Context;
+        let path = self.path.as_ref().expect("clap requires a path");
+        if self.is_tape() {
+            let bytes = std::fs::read(path).context("Failed to read file.")?;
+            let program = zx_spectrum::load_program(&bytes).context("Failed to load tape.")?;
+            return Ok(Source {
+                text: program.listing,
+                prefixed: true,
+                autostart: program.autostart,
+                variables: program.variables,
+                ..Source::default()
+            });
+        }
+        if Snapshot::is_snapshot(path) {
+            let snapshot = Snapshot::read(path)?;
+            let program = snapshot.program().context("Failed to find the program in the snapshot.")?;
+            return Ok(Source {
+                text: program.listing,
+                prefixed: true,
+                variables: program.variables,
+                memory: Some(snapshot.memory),
+                cont: program.cont,
+                ..Source::default()
+            });
+        }
+        Ok(Source {
+            text: std::fs::read_to_string(path).context("Failed to read file.")?,
+            prefixed: self.prefixed,
+            ..Source::default()
+        })
+    }
+}
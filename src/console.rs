@@ -0,0 +1,261 @@
+//! Where programs read input from and write output to. The interpreter only talks to a
+//! `Console`, so a program can run in a terminal, in tests, or behind another frontend.
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::io::{stdin, stdout, Write};
+use std::rc::Rc;
+
+pub trait TextOutput {
+    fn print(&mut self, text: &str);
+}
+
+pub trait LineInput {
+    /// Reads a line, for INPUT, without its line ending. `None` means there's no more input.
+    fn read_line(&mut self) -> Option<String>;
+}
+
+pub trait KeyPoll {
+    /// The key being pressed, if any, like INKEY$.
+    fn key(&mut self) -> Option<char>;
+}
+
+pub trait Sound {
+    /// Plays a note for `duration` seconds, `pitch` semitones above middle C, like BEEP.
+    fn beep(&mut self, duration: f64, pitch: f64);
+}
+
+pub trait Display {
+    fn cls(&mut self);
+    fn border(&mut self, colour: u8);
+    /// Moves where INPUT prints its prompt to `line` and `column` of the lower screen.
+    fn input_at(&mut self, line: u8, column: u8);
+}
+
+/// Everything a program needs to talk to the user.
+pub trait Console: TextOutput + LineInput + KeyPoll + Sound + Display + Debug {}
+
+impl<T: TextOutput + LineInput + KeyPoll + Sound + Display + Debug> Console for T {}
+
+/// The terminal the interpreter is running in, through stdin and stdout.
+#[derive(Debug, Default)]
+pub struct Terminal;
+
+impl TextOutput for Terminal {
+    fn print(&mut self, text: &str) {
+        print!("{}", text);
+        let _ = stdout().flush();
+    }
+}
+
+impl LineInput for Terminal {
+    fn read_line(&mut self) -> Option<String> {
+        let mut line = String::new();
+        match stdin().read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line.trim_end_matches(['\r', '\n']).to_string()),
+        }
+    }
+}
+
+impl KeyPoll for Terminal {
+    /// Reading a key without waiting needs the terminal in raw mode, so no key is ever pressed.
+    fn key(&mut self) -> Option<char> {
+        None
+    }
+}
+
+impl Sound for Terminal {
+    fn beep(&mut self, _duration: f64, _pitch: f64) {
+        self.print("\x07");
+    }
+}
+
+impl Display for Terminal {
+    fn cls(&mut self) {
+        // ANSI escape codes to clear the screen and move the cursor to the top-left corner
+        self.print("\x1B[2J\x1B[1;1H");
+    }
+
+    fn border(&mut self, _colour: u8) {}
+
+    /// The terminal has no lower screen, so prompts are printed where the output is.
+    fn input_at(&mut self, _line: u8, _column: u8) {}
+}
+
+/// What a program did to a `Capture`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Recording {
+    pub output: String,
+    pub beeps: Vec<(f64, f64)>,
+    pub border: Option<u8>,
+    /// How many times the screen was cleared.
+    pub clears: usize,
+    /// Where INPUT AT last moved the prompt to.
+    pub input_at: Option<(u8, u8)>,
+}
+
+/// Records output in memory, and has no input. Clones share the recording, so one can be
+/// given to the interpreter and another kept to look at what it did.
+#[derive(Debug, Default, Clone)]
+pub struct Capture {
+    recording: Rc<RefCell<Recording>>,
+}
+
+impl Capture {
+    pub fn recording(&self) -> Recording {
+        self.recording.borrow().clone()
+    }
+
+    pub fn output(&self) -> String {
+        self.recording.borrow().output.clone()
+    }
+}
+
+impl TextOutput for Capture {
+    fn print(&mut self, text: &str) {
+        self.recording.borrow_mut().output.push_str(text);
+    }
+}
+
+impl LineInput for Capture {
+    fn read_line(&mut self) -> Option<String> {
+        None
+    }
+}
+
+impl KeyPoll for Capture {
+    fn key(&mut self) -> Option<char> {
+        None
+    }
+}
+
+impl Sound for Capture {
+    fn beep(&mut self, duration: f64, pitch: f64) {
+        self.recording.borrow_mut().beeps.push((duration, pitch));
+    }
+}
+
+impl Display for Capture {
+    fn cls(&mut self) {
+        self.recording.borrow_mut().clears += 1;
+    }
+
+    fn border(&mut self, colour: u8) {
+        self.recording.borrow_mut().border = Some(colour);
+    }
+
+    fn input_at(&mut self, line: u8, column: u8) {
+        self.recording.borrow_mut().input_at = Some((line, column));
+    }
+}
+
+/// Gives a program lines to INPUT and keys to press from a script, and captures its output.
+#[derive(Debug, Default, Clone)]
+pub struct Scripted {
+    lines: VecDeque<String>,
+    keys: VecDeque<char>,
+    capture: Capture,
+}
+
+impl Scripted {
+    pub fn new<S: Into<String>>(lines: impl IntoIterator<Item = S>) -> Self {
+        Scripted {
+            lines: lines.into_iter().map(Into::into).collect(),
+            ..Scripted::default()
+        }
+    }
+
+    /// Adds keys to press, one for each time the program polls the keyboard.
+    pub fn with_keys(mut self, keys: &str) -> Self {
+        self.keys.extend(keys.chars());
+        self
+    }
+
+    /// The capture of the program's output, which stays shared with this console.
+    pub fn capture(&self) -> Capture {
+        self.capture.clone()
+    }
+}
+
+impl TextOutput for Scripted {
+    fn print(&mut self, text: &str) {
+        self.capture.print(text);
+    }
+}
+
+impl LineInput for Scripted {
+    fn read_line(&mut self) -> Option<String> {
+        self.lines.pop_front()
+    }
+}
+
+impl KeyPoll for Scripted {
+    fn key(&mut self) -> Option<char> {
+        self.keys.pop_front()
+    }
+}
+
+impl Sound for Scripted {
+    fn beep(&mut self, duration: f64, pitch: f64) {
+        self.capture.beep(duration, pitch);
+    }
+}
+
+impl Display for Scripted {
+    fn cls(&mut self) {
+        self.capture.cls();
+    }
+
+    fn border(&mut self, colour: u8) {
+        self.capture.border(colour);
+    }
+
+    fn input_at(&mut self, line: u8, column: u8) {
+        self.capture.input_at(line, column);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Capture, Console, KeyPoll, LineInput, Scripted};
+    use crate::parser::parse_file;
+    use crate::{Interpreter, Status};
+
+    fn run(source: &str, console: impl Console + 'static) -> Status {
+        let mut interpreter = Interpreter::new(parse_file(source, true).unwrap());
+        interpreter.set_console(Box::new(console));
+        interpreter.run()
+    }
+
+    #[test]
+    fn test_capture() {
+        let capture = Capture::default();
+        let status = run("10 PRINT \"a\";1,2\n20 CLS\n30 PRINT\n40 LIST 30", capture.clone());
+        assert_eq!(status, Status::Finished);
+        let recording = capture.recording();
+        assert_eq!(recording.output, "a1 2\n\n30 PRINT\n40 LIST 30\n");
+        assert_eq!(recording.clears, 1);
+    }
+
+    #[test]
+    fn test_scripted() {
+        let console = Scripted::new(["3", "4"]);
+        let capture = console.capture();
+        let program = "10 INPUT \"a?\",a\n20 INPUT b\n30 PRINT a*b\n40 INPUT c";
+        match run(program, console) {
+            Status::Stopped(report) => assert_eq!(report.to_string(), "H STOP in INPUT, 40:1"),
+            status => panic!("Expected the input to run out, found {:?}", status),
+        }
+        assert_eq!(capture.output(), "a?\n12\n");
+
+        let console = Scripted::new(["5"]);
+        let capture = console.capture();
+        assert_eq!(run("10 INPUT AT 1,4;\"a?\";a", console), Status::Finished);
+        assert_eq!(capture.recording().input_at, Some((1, 4)));
+
+        let mut console = Scripted::new(["x"]).with_keys("q");
+        assert_eq!((console.key(), console.key()), (Some('q'), None));
+        assert_eq!(console.read_line().as_deref(), Some("x"));
+    }
+}
@@ -0,0 +1,379 @@
+//! Runs a program under control: stopping at breakpoints, a statement at a time, or when a
+//! watched variable changes, so its variables and stacks can be looked at on the way.
+use std::fmt::Write;
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use crate::exec::Value;
+use crate::parser::{Expr, Line};
+use crate::source::Sources;
+use crate::{Interpreter, Status};
+
+/// Where to stop: before the statement at `line:statement`, if `condition` is true then.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Breakpoint<'a> {
+    pub line: usize,
+    pub statement: usize, // 1-based
+    pub condition: Option<Expr<'a>>,
+}
+
+/// Why the debugger gave control back.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pause {
+    /// A step finished.
+    Step,
+    /// The program reached the breakpoint with this index.
+    Breakpoint(usize),
+    /// A watched variable changed, from and to these values.
+    Watch(String, Option<i64>, Option<i64>),
+    /// The program finished or stopped.
+    Done(Status),
+}
+
+#[derive(Debug)]
+pub struct Debugger<'a> {
+    pub interpreter: Interpreter<'a>,
+    pub breakpoints: Vec<Breakpoint<'a>>,
+    /// Watched variables, with the values they had when last checked.
+    watches: Vec<(String, Option<i64>)>,
+    /// The breakpoints' conditions, as typed.
+    sources: &'a Sources,
+}
+
+fn parse_expr(source: &str) -> Result<Expr<'_>> {
+    let source = source.trim();
+    let (rest, expr) = Expr::parse(source).map_err(|e| anyhow!("Failed to parse {:?}: {:?}", source, e))?;
+    match rest.trim() {
+        "" => Ok(expr),
+        rest => Err(anyhow!("Unexpected {:?} after expression", rest)),
+    }
+}
+
+/// Parses `line[:statement] [IF condition]`.
+fn parse_breakpoint(args: &str) -> Result<Breakpoint<'_>> {
+    let (at, condition) = match args.split_once(' ') {
+        Some((at, rest)) => {
+            let rest = rest.trim_start();
+            match rest.get(..3) {
+                Some(word) if word.eq_ignore_ascii_case("if ") => (at, Some(parse_expr(&rest[3..])?)),
+                _ => bail!("Expected IF and a condition after {}, found {:?}", at, rest),
+            }
+        }
+        None => (args, None),
+    };
+    let (line, statement) = at.split_once(':').unwrap_or((at, "1"));
+    let line = line.parse().with_context(|| format!("Invalid line number {:?}", line))?;
+    let statement = statement
+        .parse()
+        .ok()
+        .filter(|&statement| statement > 0)
+        .ok_or_else(|| anyhow!("Invalid statement number {:?}", statement))?;
+    Ok(Breakpoint {
+        line,
+        statement,
+        condition,
+    })
+}
+
+const HELP: &str = "\
+break LINE[:STATEMENT] [IF CONDITION]  Stop before a statement (b)
+delete [N]                             Delete breakpoint N, or all of them
+breakpoints                            List the breakpoints
+watch NAME                             Stop when a variable changes (w)
+unwatch NAME                           Stop watching a variable
+step                                   Run one statement (s)
+next                                   Run one statement, and any GO SUB it makes (n)
+continue                               Run until a breakpoint, a watch, or the end (c)
+print EXPRESSION                       Show the value of an expression (p)
+vars                                   Show every variable
+stack                                  Show the FOR loops and the GO SUB stack (bt)
+where                                  Show the statement that runs next
+quit                                   Stop debugging (q)";
+
+impl<'a> Debugger<'a> {
+    pub fn new(interpreter: Interpreter<'a>, sources: &'a Sources) -> Self {
+        Debugger {
+            interpreter,
+            breakpoints: vec![],
+            watches: vec![],
+            sources,
+        }
+    }
+
+    /// Stops whenever `name` changes.
+    pub fn watch(&mut self, name: &str) {
+        if !self.watches.iter().any(|(watched, _)| watched.eq_ignore_ascii_case(name)) {
+            let value = self.interpreter.var(name);
+            self.watches.push((name.to_string(), value));
+        }
+    }
+
+    pub fn unwatch(&mut self, name: &str) {
+        self.watches.retain(|(watched, _)| !watched.eq_ignore_ascii_case(name));
+    }
+
+    /// The first breakpoint that is hit before the statement about to run. Conditions that
+    /// can't be evaluated, like ones using variables that aren't defined yet, aren't true.
+    fn breakpoint_hit(&self, interpreter: &Interpreter) -> Option<usize> {
+        let (line, statement) = interpreter.position()?;
+        self.breakpoints.iter().position(|breakpoint| {
+            (breakpoint.line, breakpoint.statement) == (line, statement)
+                && breakpoint.condition.as_ref().is_none_or(|condition| {
+                    matches!(interpreter.eval(condition), Ok(Value::Bool(true)))
+                })
+        })
+    }
+
+    /// The first watched variable that has changed, updating what they were last seen as.
+    fn watch_changed(&mut self) -> Option<Pause> {
+        for (name, last) in &mut self.watches {
+            let value = self.interpreter.var(name);
+            if value != *last {
+                let old = std::mem::replace(last, value);
+                return Some(Pause::Watch(name.clone(), old, value));
+            }
+        }
+        None
+    }
+
+    /// Runs until `done` returns true, a breakpoint is hit or a watched variable changes.
+    /// At least one statement is run, so continuing from a breakpoint doesn't stop at it again.
+    fn run_until(&mut self, mut done: impl FnMut(&Interpreter) -> bool) -> Pause {
+        let mut interpreter = std::mem::take(&mut self.interpreter);
+        for condition in self.breakpoints.iter_mut().filter_map(|b| b.condition.as_mut()) {
+            interpreter.intern(condition);
+        }
+        let mut breakpoint = None;
+        let status = interpreter.run_until(|interpreter| {
+            let watched = self.watches.iter().any(|(name, last)| interpreter.var(name) != *last);
+            breakpoint = self.breakpoint_hit(interpreter);
+            watched || breakpoint.is_some() || done(interpreter)
+        });
+        self.interpreter = interpreter;
+        if status != Status::Running {
+            self.watch_changed();
+            return Pause::Done(status);
+        }
+        self.watch_changed()
+            .or(breakpoint.map(Pause::Breakpoint))
+            .unwrap_or(Pause::Step)
+    }
+
+    /// Runs the next statement.
+    pub fn step(&mut self) -> Pause {
+        self.run_until(|_| true)
+    }
+
+    /// Runs the next statement, and if it's a GO SUB, the subroutine until it returns.
+    pub fn step_over(&mut self) -> Pause {
+        let depth = self.interpreter.state().gosub_stack.len();
+        self.run_until(|interpreter| interpreter.state().gosub_stack.len() <= depth)
+    }
+
+    /// Runs until a breakpoint, a watched variable changes, or the program stops.
+    pub fn cont(&mut self) -> Pause {
+        self.run_until(|_| false)
+    }
+
+    fn line_number(&self, pc: usize) -> usize {
+        self.interpreter.lines().get(pc).map_or(0, |line: &Line| line.number)
+    }
+
+    /// The FOR loops, by their control variables, and the GO SUBs the program is in, innermost
+    /// last.
+    pub fn stacks(&self) -> String {
+        let state = self.interpreter.state();
+        let mut text = String::from("FOR loops:\n");
+        let mut loops: Vec<_> = state.vars.loops().collect();
+        loops.sort_by_key(|&(symbol, _)| state.vars.name(symbol));
+        for (symbol, for_loop) in loops {
+            let _ = writeln!(
+                text,
+                "  {}={} TO {} STEP {}, from FOR at {}:{}",
+                state.vars.name(symbol),
+                state.vars.get(symbol).unwrap_or_default(),
+                for_loop.limit,
+                for_loop.step,
+                self.line_number(for_loop.pc),
+                for_loop.stmt + 1,
+            );
+        }
+        text.push_str("GO SUB stack:\n");
+        for &(pc, stmt) in &state.gosub_stack {
+            let _ = writeln!(text, "  returning to {}:{}", self.line_number(pc), stmt + 1);
+        }
+        text
+    }
+
+    /// Where the program is, and the statement that runs next.
+    pub fn position(&self) -> String {
+        match (self.interpreter.position(), self.interpreter.statement()) {
+            (Some((line, statement)), Some(instr)) => format!("{}:{} {}", line, statement, instr),
+            _ => "At the end of the program".to_string(),
+        }
+    }
+
+    /// Describes why the debugger paused, and where.
+    pub fn describe(&self, pause: &Pause) -> String {
+        let show = |value: &Option<i64>| value.map_or("undefined".to_string(), |value| value.to_string());
+        match pause {
+            Pause::Step => self.position(),
+            Pause::Breakpoint(index) => format!("Breakpoint {} at {}", index + 1, self.position()),
+            Pause::Watch(name, old, new) => {
+                format!("{} changed from {} to {}, before {}", name, show(old), show(new), self.position())
+            }
+            Pause::Done(Status::Stopped(report)) => format!("Stopped: {}", report),
+            Pause::Done(_) => "The program finished".to_string(),
+        }
+    }
+
+    /// Runs a debugger command, returning what to show. `quit` is left to the caller.
+    pub fn command(&mut self, input: &str) -> Result<String> {
+        let input = input.trim();
+        let (command, args) = input.split_once(' ').unwrap_or((input, ""));
+        let args = args.trim();
+        let pause = match command.to_ascii_lowercase().as_str() {
+            "" => return Ok(String::new()),
+            "help" | "h" => return Ok(HELP.to_string()),
+            "break" | "b" => {
+                self.breakpoints.push(parse_breakpoint(self.sources.keep(args))?);
+                return Ok(format!("Breakpoint {}", self.breakpoints.len()));
+            }
+            "delete" | "d" if args.is_empty() => {
+                self.breakpoints.clear();
+                return Ok(String::new());
+            }
+            "delete" | "d" => {
+                let index = args
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|index| (1..=self.breakpoints.len()).contains(index))
+                    .ok_or_else(|| anyhow!("No breakpoint {}", args))?;
+                self.breakpoints.remove(index - 1);
+                return Ok(String::new());
+            }
+            "breakpoints" => {
+                let mut text = String::new();
+                for (i, breakpoint) in self.breakpoints.iter().enumerate() {
+                    let _ = write!(text, "{}: {}:{}", i + 1, breakpoint.line, breakpoint.statement);
+                    if let Some(condition) = &breakpoint.condition {
+                        let _ = write!(text, " IF {}", condition);
+                    }
+                    text.push('\n');
+                }
+                return Ok(text.trim_end().to_string());
+            }
+            "watch" | "w" if !args.is_empty() => {
+                self.watch(args);
+                return Ok(format!("Watching {}", args));
+            }
+            "unwatch" if !args.is_empty() => {
+                self.unwatch(args);
+                return Ok(String::new());
+            }
+            "print" | "p" => {
+                let mut expr = parse_expr(args)?;
+                self.interpreter.intern(&mut expr);
+                return Ok(self.interpreter.eval(&expr)?.to_string());
+            }
+            "vars" => {
+                let state = self.interpreter.state();
+                let mut vars: Vec<_> = state.vars.iter().map(|(name, value)| (name, value.to_string())).collect();
+                vars.extend(state.vars.strings().map(|(name, value)| (name, format!("{:?}", value))));
+                vars.sort();
+                let vars: Vec<_> = vars.iter().map(|(name, value)| format!("{}={}", name, value)).collect();
+                return Ok(vars.join("\n"));
+            }
+            "stack" | "bt" => return Ok(self.stacks().trim_end().to_string()),
+            "where" => return Ok(self.position()),
+            "step" | "s" => self.step(),
+            "next" | "n" => self.step_over(),
+            "continue" | "c" => self.cont(),
+            _ => bail!("Unknown command {:?}, try help", input),
+        };
+        Ok(self.describe(&pause))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_breakpoint, Debugger, Pause};
+    use crate::console::Capture;
+    use crate::parser::parse_file;
+    use crate::source::Sources;
+    use crate::{Interpreter, Status};
+
+    const PROGRAM: &str = "\
+10 LET t=0
+20 FOR i=1 TO 3
+30 GO SUB 100: LET t=t+i
+40 NEXT i
+50 STOP
+100 PRINT i: LET t=t*2
+110 RETURN";
+
+    fn debugger(sources: &Sources) -> Debugger<'_> {
+        let mut interpreter = Interpreter::new(parse_file(PROGRAM, true).unwrap());
+        interpreter.set_console(Box::new(Capture::default()));
+        Debugger::new(interpreter, sources)
+    }
+
+    #[test]
+    fn test_parse_breakpoint() {
+        let breakpoint = parse_breakpoint("30:2 if i=2").unwrap();
+        assert_eq!((breakpoint.line, breakpoint.statement), (30, 2));
+        assert_eq!(breakpoint.condition.unwrap().to_string(), "i=2");
+        assert_eq!(parse_breakpoint("30").unwrap().statement, 1);
+        for invalid in ["", "x", "30:0", "30 i=2", "30 if"] {
+            assert!(parse_breakpoint(invalid).is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_step() {
+        let sources = Sources::default();
+        let mut debugger = debugger(&sources);
+        assert_eq!(debugger.position(), "10:1 LET t=0");
+        assert_eq!(debugger.step(), Pause::Step);
+        assert_eq!(debugger.step(), Pause::Step);
+        assert_eq!(debugger.position(), "30:1 GO SUB 100");
+        // Stepping goes into the subroutine, stepping over doesn't
+        assert_eq!(debugger.command("s").unwrap(), "100:1 PRINT i");
+        assert_eq!(debugger.command("n").unwrap(), "100:2 LET t=t*2");
+        assert_eq!(debugger.command("n").unwrap(), "110:1 RETURN");
+        assert_eq!(debugger.command("n").unwrap(), "30:2 LET t=t+i");
+        assert_eq!(debugger.command("n").unwrap(), "40:1 NEXT i");
+        assert_eq!(debugger.command("n").unwrap(), "30:1 GO SUB 100");
+        assert_eq!(debugger.command("n").unwrap(), "30:2 LET t=t+i");
+        assert_eq!(debugger.command("p t*10").unwrap(), "20");
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let sources = Sources::default();
+        let mut debugger = debugger(&sources);
+        debugger.command("break 100:2 IF i=2").unwrap();
+        debugger.command("b 40").unwrap();
+        assert_eq!(debugger.command("breakpoints").unwrap(), "1: 100:2 IF i=2\n2: 40:1");
+        assert_eq!(debugger.cont(), Pause::Breakpoint(1));
+        assert_eq!(debugger.interpreter.var("t"), Some(1));
+        assert_eq!(debugger.command("c").unwrap(), "Breakpoint 1 at 100:2 LET t=t*2");
+        assert_eq!(debugger.command("stack").unwrap(), "FOR loops:\n  i=2 TO 3 STEP 1, from FOR at 20:1\nGO SUB stack:\n  returning to 30:2");
+        debugger.command("delete 2").unwrap();
+        assert_eq!(debugger.command("c").unwrap(), "Stopped: 9 STOP statement, 50:1");
+        assert!(debugger.command("delete 2").is_err());
+    }
+
+    #[test]
+    fn test_watch() {
+        let sources = Sources::default();
+        let mut debugger = debugger(&sources);
+        debugger.command("watch T").unwrap();
+        assert_eq!(debugger.command("c").unwrap(), "T changed from undefined to 0, before 20:1 FOR i=1 TO 3");
+        assert_eq!(debugger.cont(), Pause::Watch("T".to_string(), Some(0), Some(1)));
+        assert_eq!(debugger.position(), "40:1 NEXT i");
+        debugger.unwatch("t");
+        assert_eq!(debugger.command("vars").unwrap(), "i=1\nt=1");
+        assert!(matches!(debugger.cont(), Pause::Done(Status::Stopped(_))));
+    }
+}
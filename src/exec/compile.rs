@@ -0,0 +1,348 @@
+use std::collections::HashMap;
+
+use crate::parser::{find_line, find_next, Expr, Ident, Instr, Line, Symbol};
+
+/// An operation of the VM, which works on a stack of numbers. Comparisons are 1 or 0.
+#[derive(Debug, Clone, Copy)]
+pub(super) enum Op<'p, 'a> {
+    /// The start of the statement at these indices into the program's lines and statements.
+    Statement(usize, usize),
+    Push(i64),
+    /// Pushes a variable, from its slot.
+    Load(usize),
+    /// Pops into a variable's slot.
+    Store(usize),
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Gt,
+    Lt,
+    Eq,
+    Ne,
+    Ge,
+    Le,
+    Jump(usize),
+    /// Pops, and jumps if it's 0.
+    JumpIfFalse(usize),
+    GoSub(usize),
+    Return,
+    /// Pops the step, the limit then the start of a FOR loop. If it doesn't run at all, it
+    /// jumps past the matching NEXT, if there is one.
+    For(usize, Option<usize>),
+    Next(usize),
+    /// Pops a number, or a comparison, onto the end of the text to PRINT.
+    AppendInt,
+    AppendBool,
+    AppendStr(&'p str),
+    /// Prints the text, and starts again with none.
+    Print,
+    Cls,
+    Stop,
+    /// Pushes the value of an expression the VM leaves to the tree-walking interpreter, like ERR.
+    Eval(&'p Expr<'a>),
+    /// Runs a statement the VM leaves to the tree-walking interpreter, which is all the ones
+    /// that are rare or slow anyway, like INPUT or LOAD, and the ones with string variables.
+    Execute(&'p Instr<'a>),
+    /// Fails as nonsense in BASIC, with the message at this index.
+    Fail(usize),
+    SyntaxError(&'a str),
+    End,
+}
+
+/// A program compiled for the VM. It borrows the lines it was compiled from.
+#[derive(Debug)]
+pub(super) struct Program<'p, 'a> {
+    pub ops: Vec<Op<'p, 'a>>,
+    /// Where each statement starts in `ops`, by line and statement. Each line has an extra
+    /// entry at the end, for where the next line starts.
+    pub starts: Vec<Vec<usize>>,
+    /// The names of the variables in each slot, as first written, for errors.
+    pub slots: Vec<Ident<'a>>,
+    /// What `Op::Fail` fails with.
+    pub messages: Vec<String>,
+}
+
+impl Program<'_, '_> {
+    /// Where the statement at these indices starts, or the next one if a line has ended.
+    pub fn target(&self, pc: usize, stmt: usize) -> usize {
+        match self.starts.get(pc) {
+            Some(starts) => starts[stmt.min(starts.len() - 1)],
+            None => self.ops.len() - 1, // Op::End
+        }
+    }
+}
+
+/// Whether an expression reads a string variable.
+fn has_string_var(expr: &Expr) -> bool {
+    match expr {
+        Expr::Ident(ident) => ident.is_string(),
+        Expr::Int(_) | Expr::String(_) | Expr::Err | Expr::Erl => false,
+        Expr::Add(a, b)
+        | Expr::Sub(a, b)
+        | Expr::Mul(a, b)
+        | Expr::Div(a, b)
+        | Expr::Gt(a, b)
+        | Expr::Lt(a, b)
+        | Expr::Eq(a, b)
+        | Expr::Ne(a, b)
+        | Expr::Ge(a, b)
+        | Expr::Le(a, b) => has_string_var(a) || has_string_var(b),
+    }
+}
+
+/// Whether a statement works with string variables, which the VM, with only numbers on its
+/// stack, leaves to the interpreter.
+fn uses_strings(instr: &Instr) -> bool {
+    match instr {
+        Instr::Print(first, rest, _) => first.iter().chain(rest.iter().map(|(_, expr)| expr)).any(has_string_var),
+        Instr::Assign(name, expr) => has_string_var(name) || has_string_var(expr),
+        Instr::IfThen(condition, _) => has_string_var(condition),
+        Instr::For(name, start, end, step) => [name, start, end, step].into_iter().any(has_string_var),
+        _ => false,
+    }
+}
+
+struct Compiler<'p, 'a> {
+    lines: &'p [Line<'a>],
+    program: Program<'p, 'a>,
+    slots: HashMap<Symbol, usize>,
+    /// Jumps, by their index in `ops`, to point at statements, by their line and statement.
+    fixups: Vec<(usize, usize, usize)>,
+    /// The indices of the line and statement being compiled.
+    at: (usize, usize),
+}
+
+impl<'p, 'a> Compiler<'p, 'a> {
+    fn emit(&mut self, op: Op<'p, 'a>) -> usize {
+        self.program.ops.push(op);
+        self.program.ops.len() - 1
+    }
+
+    fn fail(&mut self, message: String) {
+        self.program.messages.push(message);
+        self.emit(Op::Fail(self.program.messages.len() - 1));
+    }
+
+    fn slot(&mut self, name: Ident<'a>) -> usize {
+        let slots = &mut self.program.slots;
+        *self.slots.entry(name.symbol()).or_insert_with(|| {
+            slots.push(name);
+            slots.len() - 1
+        })
+    }
+
+    /// Emits a jump to the line with `number`, or the one after it, like GO TO.
+    fn jump_to_line(&mut self, op: Op<'p, 'a>, number: usize) {
+        let at = self.emit(op);
+        self.fixups.push((at, find_line(self.lines, number), 0));
+    }
+
+    /// Pushes the value of an expression used as a number.
+    fn int(&mut self, expr: &'p Expr<'a>) {
+        let (a, b, op) = match expr {
+            Expr::Ident(name) => {
+                let slot = self.slot(*name);
+                self.emit(Op::Load(slot));
+                return;
+            }
+            Expr::Int(i) => {
+                self.emit(Op::Push(*i));
+                return;
+            }
+            Expr::String(s) => return self.fail(format!("Expected integer, found string: {}", s)),
+            Expr::Err | Expr::Erl => {
+                self.emit(Op::Eval(expr));
+                return;
+            }
+            Expr::Add(a, b) => (a, b, Op::Add),
+            Expr::Sub(a, b) => (a, b, Op::Sub),
+            Expr::Mul(a, b) => (a, b, Op::Mul),
+            Expr::Div(a, b) => (a, b, Op::Div),
+            Expr::Gt(..) | Expr::Lt(..) | Expr::Eq(..) | Expr::Ne(..) | Expr::Ge(..) | Expr::Le(..) => {
+                return self.fail(format!("Expected integer, found comparison: {:?}", expr))
+            }
+        };
+        self.int(a);
+        self.int(b);
+        self.emit(op);
+    }
+
+    /// Pushes 1 or 0 for a comparison, returning false if the expression isn't one.
+    fn comparison(&mut self, expr: &'p Expr<'a>) -> bool {
+        let (a, b, op) = match expr {
+            Expr::Gt(a, b) => (a, b, Op::Gt),
+            Expr::Lt(a, b) => (a, b, Op::Lt),
+            Expr::Eq(a, b) => (a, b, Op::Eq),
+            Expr::Ne(a, b) => (a, b, Op::Ne),
+            Expr::Ge(a, b) => (a, b, Op::Ge),
+            Expr::Le(a, b) => (a, b, Op::Le),
+            _ => return false,
+        };
+        self.int(a);
+        self.int(b);
+        self.emit(op);
+        true
+    }
+
+    /// Appends the value of an expression to the text to PRINT.
+    fn print_value(&mut self, expr: &'p Expr<'a>) {
+        if let Expr::String(s) = expr {
+            self.emit(Op::AppendStr(s));
+        } else if self.comparison(expr) {
+            self.emit(Op::AppendBool);
+        } else {
+            self.int(expr);
+            self.emit(Op::AppendInt);
+        }
+    }
+
+    fn separator(&mut self, separator: char) {
+        match separator {
+            ',' => {
+                self.emit(Op::AppendStr(" "));
+            }
+            ';' => {}
+            _ => self.fail(format!("Expected ',' or ';', found: {:?}", separator)),
+        }
+    }
+
+    fn statement(&mut self, instr: &'p Instr<'a>) {
+        if uses_strings(instr) {
+            self.emit(Op::Execute(instr));
+            return;
+        }
+        match instr {
+            Instr::Print(first, rest, last) => {
+                if let Some(first) = first {
+                    self.print_value(first);
+                    for (separator, expr) in rest {
+                        self.separator(*separator);
+                        self.print_value(expr);
+                    }
+                }
+                match last {
+                    Some(last) => self.separator(*last),
+                    None => {
+                        self.emit(Op::AppendStr("\n"));
+                    }
+                }
+                self.emit(Op::Print);
+            }
+            Instr::Assign(Expr::Ident(name), expr) => {
+                self.int(expr);
+                let slot = self.slot(*name);
+                self.emit(Op::Store(slot));
+            }
+            Instr::Assign(expr, _) => {
+                self.fail(format!("(In assignment instr) Expected identifier, found: {:?}", expr))
+            }
+            Instr::Rem(_) => {}
+            Instr::Goto(number) => self.jump_to_line(Op::Jump(0), *number),
+            Instr::Gosub(number) => self.jump_to_line(Op::GoSub(0), *number),
+            Instr::Return => {
+                self.emit(Op::Return);
+            }
+            Instr::Stop => {
+                self.emit(Op::Stop);
+            }
+            Instr::Cls => {
+                self.emit(Op::Cls);
+            }
+            Instr::IfThen(condition, then) => {
+                if !self.comparison(condition) {
+                    if !matches!(condition, Expr::String(_)) {
+                        self.int(condition);
+                    }
+                    self.fail(format!("Expected boolean, found: {:?}", condition));
+                }
+                // A false IF skips the rest of the line
+                let at = self.emit(Op::JumpIfFalse(0));
+                self.fixups.push((at, self.at.0 + 1, 0));
+                self.statement(then);
+            }
+            Instr::Multi(instrs) => {
+                for instr in instrs {
+                    self.statement(instr);
+                }
+            }
+            Instr::For(Expr::Ident(name), start, end, step) => {
+                self.int(start);
+                self.int(end);
+                self.int(step);
+                let (pc, stmt) = self.at;
+                let slot = self.slot(*name);
+                match find_next(self.lines, name.symbol(), pc, stmt) {
+                    Some((pc, stmt)) => {
+                        let at = self.emit(Op::For(slot, Some(0)));
+                        self.fixups.push((at, pc, stmt + 1));
+                    }
+                    None => {
+                        self.emit(Op::For(slot, None));
+                    }
+                }
+            }
+            Instr::Next(Expr::Ident(name)) => {
+                let slot = self.slot(*name);
+                self.emit(Op::Next(slot));
+            }
+            Instr::For(expr, ..) | Instr::Next(expr) => {
+                self.fail(format!("Expected identifier, found {:?}", expr))
+            }
+            Instr::SyntaxError(text) => {
+                self.emit(Op::SyntaxError(text));
+            }
+            Instr::Input(..)
+            | Instr::List(_)
+            | Instr::Run(_)
+            | Instr::Clear
+            | Instr::Continue
+            | Instr::Tape(..)
+            | Instr::OnErrorGoto(_)
+            | Instr::Resume(_)
+            | Instr::Renumber(..) => {
+                self.emit(Op::Execute(instr));
+            }
+        }
+    }
+}
+
+/// Compiles a program: its variables to slots, its expressions to stack operations, and the
+/// lines it jumps to, to where their first statement starts.
+pub(super) fn compile<'p, 'a>(lines: &'p [Line<'a>]) -> Program<'p, 'a> {
+    let mut compiler = Compiler {
+        lines,
+        program: Program {
+            ops: vec![],
+            starts: vec![],
+            slots: vec![],
+            messages: vec![],
+        },
+        slots: HashMap::new(),
+        fixups: vec![],
+        at: (0, 0),
+    };
+    for (pc, line) in lines.iter().enumerate() {
+        let mut starts = vec![];
+        for (stmt, instr) in line.instr.statements().iter().enumerate() {
+            starts.push(compiler.emit(Op::Statement(pc, stmt)));
+            compiler.at = (pc, stmt);
+            compiler.statement(instr);
+        }
+        starts.push(compiler.program.ops.len());
+        compiler.program.starts.push(starts);
+    }
+    compiler.emit(Op::End);
+
+    let mut program = compiler.program;
+    for (at, pc, stmt) in compiler.fixups {
+        let target = program.target(pc, stmt);
+        program.ops[at] = match program.ops[at] {
+            Op::GoSub(_) => Op::GoSub(target),
+            Op::JumpIfFalse(_) => Op::JumpIfFalse(target),
+            Op::For(slot, _) => Op::For(slot, Some(target)),
+            _ => Op::Jump(target),
+        };
+    }
+    program
+}
@@ -1,51 +1,146 @@
-use anyhow::{anyhow, ensure, Result};
+use anyhow::{anyhow, Result};
 
-use super::{state::LoopState, State, Value};
-use crate::parser::{Expr, Instr};
+use super::input::execute_input;
+use super::interrupt::take_break;
+use super::tape::execute_tape;
+use super::vm;
+use super::{state::LoopState, Dialect, ErrorCode, Report, State, Value};
+use crate::parser::{find_line, find_next, Expr, Instr, Line};
+use crate::renumber::{line_number, renumber};
 
-pub fn execute(instrs: Vec<Instr>) -> Result<(), anyhow::Error> {
-    let mut state = State::default();
-    while state.pc < instrs.len() {
-        if !instrs[state.pc].execute(&mut state)? {
-            state.pc += 1;
+/// Runs the program from `state.pc` until it ends. The state is kept, so the program can
+/// be inspected, or continued after a STOP, BREAK or error with `State::resume`.
+pub fn run<'a>(lines: &mut Vec<Line<'a>>, state: &mut State<'a>) -> Result<(), Report> {
+    take_break();
+    match state.tracer {
+        // Only `step` tells the tracer what's happening
+        Some(_) => while step(lines, state)? {},
+        None => vm::run(lines, state)?,
+    }
+    Ok(())
+}
+
+/// Where the program goes after a statement.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum Flow {
+    /// On to the next statement.
+    Next,
+    /// To where the statement moved `state.pc` and `state.stmt`.
+    Jump,
+    /// To the next line, as after an IF that's false.
+    SkipLine,
+}
+
+/// Moves `state` past the ends of lines, so it's at the next statement to run, if any.
+fn skip_line_ends(lines: &[Line], state: &mut State) {
+    while state.pc < lines.len() && state.stmt >= lines[state.pc].instr.statements().len() {
+        state.jump(state.pc + 1, 0);
+    }
+}
+
+/// Runs the statement at `state.pc` and `state.stmt`. Returns whether there is more of the
+/// program to run.
+pub fn step<'a>(lines: &mut Vec<Line<'a>>, state: &mut State<'a>) -> Result<bool, Report> {
+    skip_line_ends(lines, state);
+    let Some(line) = lines.get(state.pc) else {
+        return Ok(false);
+    };
+    let instr = &line.instr.statements()[state.stmt];
+    let result = match take_break() {
+        true => Err(ErrorCode::Break.into()),
+        // REM is left out of the compiled program, so it doesn't count towards the limits
+        false => match instr {
+            Instr::Rem(_) => Ok(()),
+            _ => state.limits.statement(&mut state.usage),
+        }
+        .and_then(|_| {
+            // Only statements that actually run are traced
+            if let Some(tracer) = &mut state.tracer {
+                tracer.statement(line.number, state.stmt + 1, instr);
+            }
+            instr.execute(state, lines)
+        }),
+    };
+    let number = line.number;
+    if let Some(loaded) = state.loaded.take() {
+        *lines = loaded;
+    }
+    match result {
+        Ok(Flow::Jump) => {}
+        Ok(Flow::Next) => state.stmt += 1,
+        Ok(Flow::SkipLine) => state.jump(state.pc + 1, 0),
+        Err(err) => {
+            let report = Report::new(err, number, state.stmt + 1);
+            if state.trap(&report, state.pc, state.stmt, lines) {
+                skip_line_ends(lines, state);
+                return Ok(state.pc < lines.len());
+            }
+            let stmt = state.stmt + report.code.continues_after() as usize;
+            state.cont = Some((state.pc, stmt));
+            return Err(report);
+        }
+    }
+    skip_line_ends(lines, state);
+    Ok(state.pc < lines.len())
+}
+
+/// Runs a statement typed without a line number. If it jumps into the program (GO TO, RUN,
+/// CONTINUE, ...) the program then runs from there.
+pub fn execute_immediate<'a>(
+    instr: &Instr,
+    lines: &mut Vec<Line<'a>>,
+    state: &mut State<'a>,
+) -> Result<(), Report> {
+    take_break();
+    for (i, statement) in instr.statements().iter().enumerate() {
+        let flow = statement.execute(state, lines);
+        if let Some(loaded) = state.loaded.take() {
+            *lines = loaded;
+        }
+        match flow {
+            Ok(Flow::Jump) => return run(lines, state),
+            Ok(Flow::Next) => {}
+            Ok(Flow::SkipLine) => break,
+            Err(err) => return Err(Report::new(err, 0, i + 1)),
         }
     }
     Ok(())
 }
 
-impl<'a> Instr<'a> {
-    fn execute<'b>(&self, state: &mut State<'b>) -> Result<bool>
-    // Did we jump?
-    where
-        'a: 'b,
-    {
+impl Instr<'_> {
+    pub(super) fn execute<'b>(&self, state: &mut State<'b>, lines: &[Line<'b>]) -> Result<Flow> {
         match self {
             Instr::Print(first, rest, last) => {
+                let mut text = String::new();
                 if let Some(first) = first {
-                    print!("{}", first.eval(state)?);
+                    text.push_str(&first.eval(state)?.to_string());
                     for (sep, expr) in rest {
                         match *sep {
-                            ',' => print!(" "),
+                            ',' => text.push(' '),
                             ';' => {},
                             _ => return Err(anyhow!("Expected ',' or ';', found: {:?}", sep)),
                         }
-                        print!("{}", expr.eval(state)?);
-                    }
-                    if let Some(last) = last {
-                        match *last {
-                            ',' => print!(" "),
-                            ';' => {},
-                            _ => return Err(anyhow!("Expected ',' or ';', found: {:?}", last)),
-                        }
-                    } else {
-                        println!();
+                        text.push_str(&expr.eval(state)?.to_string());
                     }
-                } else {
-                    println!();
                 }
+                match last {
+                    Some(',') => text.push(' '),
+                    Some(';') => {}
+                    Some(last) => return Err(anyhow!("Expected ',' or ';', found: {:?}", last)),
+                    None => text.push('\n'),
+                }
+                state.print(&text)?;
+            }
+            Instr::Assign(Expr::Ident(ident), expr) if ident.is_string() => {
+                let value = match expr.eval(state)? {
+                    Value::String(value) => value.to_string(),
+                    other => return Err(anyhow!("Expected a string, found: {}", other)),
+                };
+                state.set_string(ident, value)?;
             }
             Instr::Assign(Expr::Ident(ident), expr) => {
-                state.vars.insert(ident.clone(), expr.eval_to_int(state)?);
+                let value = expr.eval_to_int(state)?;
+                state.set_var(ident, value)?;
             }
             Instr::Assign(expr, _) => {
                 return Err(anyhow!(
@@ -54,98 +149,130 @@ impl<'a> Instr<'a> {
                 ))
             }
             Instr::Rem(_) => {}
-            Instr::Input(expr1, Expr::Ident(ident)) => {
-                // TODO: If the ident ends in $, print a quotation mark before input, and expect a string
-                if let Some(expr) = expr1 {
-                    println!("{}", expr.eval(state)?);
-                }
-                let mut input = String::new();
-                std::io::stdin().read_line(&mut input)?;
-                // TODO: Impl "CONTINUE"
-                if input.trim_end() == "STOP" {
-                    return Err(anyhow!("Program stopped by user"));
+            Instr::Input(items) => execute_input(items, state)?,
+            Instr::Goto(number) => {
+                state.jump(find_line(lines, *number), 0);
+                return Ok(Flow::Jump);
+            }
+            Instr::Gosub(number) => {
+                state.gosub_stack.push((state.pc, state.stmt + 1));
+                state.jump(find_line(lines, *number), 0);
+                return Ok(Flow::Jump);
+            }
+            Instr::Stop => return Err(ErrorCode::Stop.into()),
+            Instr::Return => {
+                let (pc, stmt) = state
+                    .gosub_stack
+                    .pop()
+                    .ok_or(ErrorCode::ReturnWithoutGosub)?;
+                state.jump(pc, stmt);
+                return Ok(Flow::Jump);
+            }
+            Instr::List(first) => {
+                for line in &lines[find_line(lines, first.unwrap_or(0))..] {
+                    state.print(&format!("{}\n", line))?;
                 }
-                let input = input.trim_end().parse::<i64>()?;
-                state.vars.insert(ident.clone(), input);
             }
-            Instr::Input(_, expr) => {
-                return Err(anyhow!(
-                    "(In input instr) Expected identifier, found: {:?}",
-                    expr
-                ))
+            Instr::Run(first) => {
+                state.clear();
+                state.on_error = None;
+                state.handling = None;
+                state.trapped = None;
+                state.jump(find_line(lines, first.unwrap_or(0)), 0);
+                return Ok(Flow::Jump);
+            }
+            Instr::Clear => state.clear(),
+            Instr::Continue => {
+                state.resume()?;
+                return Ok(Flow::Jump);
             }
-            Instr::Goto(pc) => {
-                state.pc = (*pc / 10) - 1;
-                return Ok(true);
-            } // Convert from line number to 0-based index. Non-10s digits are ignored.
-            Instr::Clear => print!("\x1B[2J\x1B[1;1H"), // ANSI escape codes to clear the screen and move the cursor to the top-left corner
+            Instr::Cls => state.console.cls(),
             Instr::IfThen(expr, if_true) => match expr.eval(state)? {
-                Value::Bool(true) => return if_true.execute(state),
-                Value::Bool(false) => {}
+                Value::Bool(true) => return if_true.execute(state, lines),
+                Value::Bool(false) => return Ok(Flow::SkipLine),
                 _ => return Err(anyhow!("Expected boolean, found: {:?}", expr)),
             },
             Instr::Multi(instrs) => {
                 for instr in instrs {
-                    if instr.execute(state)? {
-                        return Ok(true);
+                    match instr.execute(state, lines)? {
+                        Flow::Next => {}
+                        flow => return Ok(flow),
                     }
                 }
             }
             Instr::For(Expr::Ident(ident), start, end, step) => {
-                state.vars.insert(ident.clone(), start.eval_to_int(state)?);
-                state.loop_stack.push(LoopState {
-                    start_line: state.pc,
-                    var_name: ident.clone(),
-                    end_value: end.eval_to_int(state)?,
+                let start = start.eval_to_int(state)?;
+                let for_loop = LoopState {
+                    limit: end.eval_to_int(state)?,
                     step: step.eval_to_int(state)?,
-                });
+                    pc: state.pc,
+                    stmt: state.stmt,
+                };
+                if !state.start_loop(ident, start, for_loop)? {
+                    let (pc, stmt) = find_next(lines, ident.symbol(), state.pc, state.stmt).ok_or(ErrorCode::ForWithoutNext)?;
+                    state.jump(pc, stmt + 1);
+                    return Ok(Flow::Jump);
+                }
             }
             Instr::For(expr, _, _, _) => {
                 return Err(anyhow!("Expected identifier, found {:?}", expr));
             }
             Instr::Next(Expr::Ident(ident)) => {
-                let loop_state = state
-                    .loop_stack
-                    .last_mut()
-                    .ok_or(anyhow!("Next without for"))?;
-                ensure!(
-                    loop_state.var_name == *ident,
-                    "Next without matching for: {}",
-                    ident
-                );
-                state.vars.insert(
-                    ident.clone(),
-                    state
-                        .vars
-                        .get(ident)
-                        .ok_or(anyhow!("NameError: {}", ident))?
-                        + loop_state.step,
-                );
-                if loop_state.step == 0 {
-                    return Err(anyhow!("Step cannot be zero")); // TODO: Relax this restriction
-                } else if (loop_state.step > 0
-                    && state.vars.get(ident) <= Some(&loop_state.end_value))
-                    || (loop_state.step < 0 && state.vars.get(ident) >= Some(&loop_state.end_value))
-                {
-                    state.pc = loop_state.start_line + 1;
-
-                    return Ok(true);
+                if let Some((pc, stmt)) = state.next(ident)? {
+                    state.jump(pc, stmt);
+                    return Ok(Flow::Jump);
                 }
             }
             Instr::Next(expr) => {
                 return Err(anyhow!("Expected identifier, found {:?}", expr));
             }
+            Instr::Tape(command, name, data) => {
+                if execute_tape(*command, name, data, state, lines)? {
+                    return Ok(Flow::Jump);
+                }
+            }
+            Instr::OnErrorGoto(_) | Instr::Resume(_) if state.dialect != Dialect::Extended => {
+                return Err(ErrorCode::Nonsense.with(format!("{} is only in the extended dialect", self.keyword())));
+            }
+            Instr::OnErrorGoto(number) => {
+                // A handler that leaves with GO TO rather than RESUME traps errors again
+                // once it sets the handler again
+                state.handling = None;
+                state.on_error = Some(*number).filter(|&number| number != 0);
+            }
+            Instr::Resume(next) => {
+                let (pc, stmt) = state
+                    .handling
+                    .take()
+                    .ok_or_else(|| ErrorCode::Nonsense.with("RESUME without an error"))?;
+                state.jump(pc, stmt + *next as usize);
+                return Ok(Flow::Jump);
+            }
+            Instr::Renumber(start, step) => {
+                let (start, step) = (start.unwrap_or(10), step.unwrap_or(10));
+                let program = renumber(lines, start, step)?;
+                // The lines stay in order, so only the line ON ERROR goes to has to change
+                if let Some(number) = state.on_error {
+                    let number = line_number(find_line(lines, number), start, step);
+                    state.on_error = Some(number.ok_or_else(|| {
+                        ErrorCode::IntegerOutOfRange.with(format!("RENUMBER {},{}", start, step))
+                    })?);
+                }
+                state.loaded = Some(program);
+            }
+            Instr::SyntaxError(line) => return Err(ErrorCode::Nonsense.with(line)),
         }
-        Ok(false)
+        Ok(Flow::Next)
     }
 }
 
 impl Expr<'_> {
-    fn eval(&self, state: &State) -> Result<Value> {
+    pub(crate) fn eval<'e>(&'e self, state: &'e State) -> Result<Value<'e>> {
         match self {
+            Expr::Ident(ident) if ident.is_string() => Ok(state.get_string(ident)?.into()),
             Expr::Ident(ident) => Ok(state.get_var(ident)?.into()),
             Expr::Int(i) => Ok((*i).into()),
-            Expr::Add(_, _) | Expr::Sub(_, _) | Expr::Mul(_, _) | Expr::Div(_, _) => {
+            Expr::Add(_, _) | Expr::Sub(_, _) | Expr::Mul(_, _) | Expr::Div(_, _) | Expr::Err | Expr::Erl => {
                 Ok(self.eval_to_int(state)?.into())
             }
             Expr::String(s) => Ok(Value::String(s)),
@@ -170,14 +297,31 @@ impl Expr<'_> {
         }
     }
 
-    fn eval_to_int(&self, state: &State) -> Result<i64> {
+    pub(super) fn eval_to_int(&self, state: &State) -> Result<i64> {
         match self {
             Expr::Ident(ident) => Ok(state.get_var(ident)?),
             Expr::Int(i) => Ok(*i),
-            Expr::Add(expr1, expr2) => Ok(expr1.eval_to_int(state)? + expr2.eval_to_int(state)?),
-            Expr::Sub(expr1, expr2) => Ok(expr1.eval_to_int(state)? - expr2.eval_to_int(state)?),
-            Expr::Mul(expr1, expr2) => Ok(expr1.eval_to_int(state)? * expr2.eval_to_int(state)?),
-            Expr::Div(expr1, expr2) => Ok(expr1.eval_to_int(state)? / expr2.eval_to_int(state)?),
+            Expr::Add(expr1, expr2) => Ok(expr1
+                .eval_to_int(state)?
+                .checked_add(expr2.eval_to_int(state)?)
+                .ok_or(ErrorCode::NumberTooBig)?),
+            Expr::Sub(expr1, expr2) => Ok(expr1
+                .eval_to_int(state)?
+                .checked_sub(expr2.eval_to_int(state)?)
+                .ok_or(ErrorCode::NumberTooBig)?),
+            Expr::Mul(expr1, expr2) => Ok(expr1
+                .eval_to_int(state)?
+                .checked_mul(expr2.eval_to_int(state)?)
+                .ok_or(ErrorCode::NumberTooBig)?),
+            Expr::Div(expr1, expr2) => Ok(expr1
+                .eval_to_int(state)?
+                .checked_div(expr2.eval_to_int(state)?)
+                .ok_or(ErrorCode::NumberTooBig)?),
+            Expr::Err | Expr::Erl if state.dialect != Dialect::Extended => {
+                Err(ErrorCode::Nonsense.with(format!("{} is only in the extended dialect", self)))
+            }
+            Expr::Err => Ok(state.trapped.as_ref().map_or(0, |report| report.code.number() as i64)),
+            Expr::Erl => Ok(state.trapped.as_ref().map_or(0, |report| report.line as i64)),
             Expr::String(s) => Err(anyhow!("Expected integer, found string: {}", s)),
             Expr::Gt(_, _)
             | Expr::Lt(_, _)
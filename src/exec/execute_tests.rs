@@ -0,0 +1,173 @@
+#[cfg(test)]
+mod tests {
+    use crate::console::Scripted;
+    use crate::exec::test_fixture::run_both;
+    use crate::exec::{Dialect, Limits};
+    use crate::parser::{parse_file_recovering, Instr};
+    use crate::Interpreter;
+
+    /// What a program prints, then how it ended, the same compiled or run a statement at a time.
+    fn run(program: &str, input: &[&str]) -> String {
+        run_in(Dialect::Spectrum, program, input)
+    }
+
+    fn run_in(dialect: Dialect, program: &str, input: &[&str]) -> String {
+        let limits = Limits {
+            statements: Some(10_000),
+            ..Limits::default()
+        };
+        run_both(program, input, dialect, limits)
+    }
+
+    #[test]
+    fn test_for_next() {
+        let nested = "10 FOR i=1 TO 3: FOR j=1 TO i: PRINT i;j;\" \";: NEXT j: NEXT i";
+        assert_eq!(run(nested, &[]), "11 21 22 31 32 33 Finished");
+        let down = "10 FOR i=10 TO 1 STEP -4\n20 PRINT i;\" \";\n30 NEXT i\n40 PRINT i";
+        assert_eq!(run(down, &[]), "10 6 2 -2\nFinished");
+        // The limit and step are worked out before the variable is set
+        let limit = "10 LET i=3\n20 FOR i=1 TO i+1: PRINT i;: NEXT i";
+        assert_eq!(run(limit, &[]), "1234Finished");
+    }
+
+    #[test]
+    fn test_names() {
+        // Names are the same variable whatever their case or spaces, compiled or not
+        let program = "10 LET My Total=1\n20 FOR I=1 TO 3\n30 LET mytotal=MYTOTAL*2: NEXT i\n40 PRINT my total";
+        assert_eq!(run(program, &[]), "8\nFinished");
+        let skip = "10 FOR N=2 TO 1\n20 NEXT n: PRINT N";
+        assert_eq!(run(skip, &[]), "2\nFinished");
+    }
+
+    #[test]
+    fn test_for_skips() {
+        // A loop that doesn't run skips past its NEXT, and leaves the variable at the start
+        let skip = "10 FOR i=5 TO 1\n20 PRINT \"in\"\n30 NEXT i: PRINT \"out\";i";
+        assert_eq!(run(skip, &[]), "out5\nFinished");
+        assert_eq!(run("10 FOR i=1 TO 0: NEXT j\n20 PRINT i", &[]), "I FOR without NEXT, 10:1");
+        // Its NEXT can come after THEN
+        let then = "10 FOR i=5 TO 1\n20 IF c THEN NEXT i: PRINT \"after\"\n30 PRINT \"out\"";
+        assert_eq!(run(then, &[]), "after\nout\nFinished");
+    }
+
+    #[test]
+    fn test_next_finds_its_for() {
+        // NEXT loops back to its own FOR, even past another loop's FOR
+        let crossed = "10 FOR i=1 TO 2\n20 FOR j=1 TO 3\n30 PRINT i;j;\" \";\n40 NEXT i";
+        assert_eq!(run(crossed, &[]), "11 21 Finished");
+        // FOR again resets the loop
+        let reset = "10 LET n=0\n20 FOR i=1 TO 3\n30 LET n=n+1\n40 IF n=2 THEN GO TO 20\n50 NEXT i\n60 PRINT n;i";
+        assert_eq!(run(reset, &[]), "54\nFinished");
+        assert_eq!(run("10 LET i=1\n20 NEXT i", &[]), "1 NEXT without FOR, 20:1 (i isn't the variable of a FOR loop)");
+        assert_eq!(run("10 NEXT i", &[]), "2 Variable not found, 10:1 (i)");
+    }
+
+    #[test]
+    fn test_if_then() {
+        let skip = "10 LET a=1\n20 IF a=2 THEN PRINT 1: PRINT 2\n30 IF a=1 THEN PRINT 3: PRINT 4";
+        assert_eq!(run(skip, &[]), "3\n4\nFinished");
+        // A loop on the same line goes back to the statement after the FOR, not the IF
+        let loops = "10 LET n=0\n20 IF n=0 THEN FOR i=1 TO 3: LET n=n+1: NEXT i: PRINT n\n30 IF n>1 THEN GO TO 50: PRINT \"no\"\n40 PRINT \"no\"\n50 STOP";
+        assert_eq!(run(loops, &[]), "3\n9 STOP statement, 50:1");
+        // Errors in the rest of the line are reported at their own statement
+        assert_eq!(run("10 IF 1=1 THEN PRINT 1: PRINT x", &[]), "1\n2 Variable not found, 10:2 (x)");
+    }
+
+    #[test]
+    fn test_immediate_if() {
+        let mut interpreter = Interpreter::new(parse_file_recovering("10 PRINT \"ran\"", true).0);
+        let console = Scripted::new(Vec::<String>::new());
+        let capture = console.capture();
+        interpreter.set_console(Box::new(console));
+        let (_, instr) = Instr::parse("IF 1=2 THEN PRINT 1: GO TO 10").unwrap();
+        interpreter.execute(&instr).unwrap();
+        assert_eq!(capture.output(), "");
+    }
+
+    #[test]
+    fn test_step_zero() {
+        let forever = "10 FOR i=1 TO 2 STEP 0\n20 NEXT i";
+        assert_eq!(
+            run(forever, &[]),
+            "X Limit exceeded, 20:1 (More than 10000 statements)"
+        );
+        // Unless it starts past the limit
+        assert_eq!(run("10 FOR i=3 TO 2 STEP 0\n20 NEXT i\n30 PRINT i", &[]), "3\nFinished");
+    }
+
+    #[test]
+    fn test_input() {
+        let program = "10 INPUT \"Name? \";n$;\" Age? \";a\n20 PRINT n$;\" \";a";
+        assert_eq!(run(program, &["Ann", "40"]), "Name? \n Age? \nAnn 40\nFinished");
+        // Numbers are expressions, and strings are typed between quote marks
+        let program = "10 LET k=2\n20 INPUT a,b$\n30 PRINT a;b$";
+        assert_eq!(run(program, &["k*3+1", "say \"\"hi\"\""]), "7say \"hi\"\nFinished");
+        assert_eq!(run(program, &["j"]), "2 Variable not found, 20:1 (j)");
+        // LINE takes what's typed as it is
+        assert_eq!(run("10 INPUT LINE a$\n20 PRINT a$", &["\"quoted\""]), "\"quoted\"\nFinished");
+        assert_eq!(run("10 INPUT a\n20 PRINT a", &["STOP"]), "H STOP in INPUT, 10:1");
+    }
+
+    #[test]
+    fn test_input_again() {
+        // Nonsense, or the wrong type, is asked for again
+        let program = "10 INPUT \"n?\";n,\"s?\";s$\n20 PRINT n;s$";
+        assert_eq!(run(program, &["1+", "\"x\"", "5", "a\"b", "ok"]), "n?\nn?\nn?\n s?\n s?\n5ok\nFinished");
+    }
+
+    #[test]
+    fn test_on_error() {
+        let program = "10 ON ERROR GO TO 100\n20 LET a=1/0\n30 PRINT \"a=\";a\n40 PRINT x\n50 STOP\n100 PRINT err;\" \";erl\n110 IF err=6 THEN LET a=5: RESUME NEXT\n120 LET x=3: RESUME";
+        assert_eq!(run_in(Dialect::Extended, program, &[]), "6 20\na=5\n2 40\n3\n9 STOP statement, 50:1");
+        // Errors in the handler, and STOP, aren't trapped
+        let nested = "10 ON ERROR GO TO 100\n20 PRINT x\n100 PRINT y";
+        assert_eq!(run_in(Dialect::Extended, nested, &[]), "2 Variable not found, 100:1 (y)");
+        let stop = "10 ON ERROR GO TO 100\n20 STOP\n100 PRINT \"trapped\"";
+        assert_eq!(run_in(Dialect::Extended, stop, &[]), "9 STOP statement, 20:1");
+        // ON ERROR GO TO 0 turns trapping off again
+        let off = "10 ON ERROR GO TO 100\n20 ON ERROR GO TO 0\n30 PRINT x\n100 PRINT \"trapped\"";
+        assert_eq!(run_in(Dialect::Extended, off, &[]), "2 Variable not found, 30:1 (x)");
+        assert_eq!(
+            run_in(Dialect::Extended, "10 RESUME", &[]),
+            "C Nonsense in BASIC, 10:1 (RESUME without an error)"
+        );
+        // The Spectrum has none of it
+        assert_eq!(
+            run("10 ON ERROR GO TO 100\n100 STOP", &[]),
+            "C Nonsense in BASIC, 10:1 (ON ERROR is only in the extended dialect)"
+        );
+        assert_eq!(run("10 PRINT ERL", &[]), "C Nonsense in BASIC, 10:1 (ERL is only in the extended dialect)");
+        // ERR and ERL aren't variables, so don't clobber any
+        let vars = "10 LET er=1: ON ERROR GO TO 100\n20 PRINT x\n100 PRINT ERR;ERL;er";
+        assert_eq!(run_in(Dialect::Extended, vars, &[]), "2201\nFinished");
+        assert_eq!(run_in(Dialect::Extended, "10 PRINT ERR;ERL", &[]), "00\nFinished");
+    }
+
+    #[test]
+    fn test_on_error_again() {
+        // A handler that leaves with GO TO doesn't trap errors until ON ERROR GO TO runs again
+        let left = "10 ON ERROR GO TO 100\n20 PRINT x\n30 PRINT y\n100 GO TO 30";
+        assert_eq!(run_in(Dialect::Extended, left, &[]), "2 Variable not found, 30:1 (y)");
+        let again = "10 ON ERROR GO TO 100\n20 PRINT x\n30 PRINT y\n40 STOP\n100 PRINT ERL\n110 ON ERROR GO TO 100: IF ERL=20 THEN GO TO 30";
+        assert_eq!(run_in(Dialect::Extended, again, &[]), "20\n30\nFinished");
+    }
+
+    #[test]
+    fn test_overflow() {
+        for program in ["10 LET a=9223372036854775807\n20 LET b=a+1", "10 LET a=4294967296\n20 PRINT a*a"] {
+            assert_eq!(run(program, &[]), "6 Number too big, 20:1");
+        }
+    }
+
+    #[test]
+    fn test_renumber() {
+        let program = "10 ON ERROR GO TO 30\n20 RENUMBER: PRINT x\n30 PRINT ERR: LIST";
+        assert_eq!(
+            run_in(Dialect::Extended, program, &[]),
+            "2\n10 ON ERROR GO TO 30\n20 RENUMBER: PRINT x\n30 PRINT ERR: LIST\nFinished"
+        );
+        let program = "5 RENUMBER 100,1: PRINT 1\n7 RETURN";
+        assert_eq!(run(program, &[]), "1\n7 RETURN without GO SUB, 101:1");
+        assert_eq!(run("1 RENUMBER 1,9999\n2 STOP", &[]), "B Integer out of range, 1:1 (RENUMBER 1,9999)");
+    }
+}
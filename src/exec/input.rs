@@ -0,0 +1,109 @@
+use anyhow::Result;
+use nom::combinator::all_consuming;
+
+use super::{ErrorCode, State, Value};
+use crate::parser::{Expr, Ident, InputItem};
+
+/// A value typed in for a variable.
+enum Typed {
+    Number(i64),
+    String(String),
+}
+
+/// Works out what was typed for a variable, or `None` if it's nonsense in BASIC, so has to be
+/// typed again. A number can be any numeric expression, like `2*3` or another variable.
+fn typed_value(typed: &str, ident: &Ident, line: bool, state: &mut State) -> Result<Option<Typed>> {
+    if line {
+        return Ok(Some(Typed::String(typed.to_string())));
+    }
+    if ident.is_string() {
+        // It's typed between quote marks, so a quote mark in it has to be doubled
+        let mut text = String::new();
+        let mut chars = typed.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c == '"' && chars.next_if_eq(&'"').is_none() {
+                return Ok(None);
+            }
+            text.push(c);
+        }
+        return Ok(Some(Typed::String(text)));
+    }
+    if typed.trim() == "STOP" {
+        return Err(ErrorCode::StopInInput.into());
+    }
+    let Ok((_, mut expr)) = all_consuming(Expr::parse)(typed.trim()) else {
+        return Ok(None);
+    };
+    state.vars.intern_expr(&mut expr);
+    match expr.eval(state) {
+        Ok(Value::Int(value)) => Ok(Some(Typed::Number(value))),
+        Ok(Value::Bool(value)) => Ok(Some(Typed::Number(value as i64))),
+        Ok(_) => Ok(None),
+        Err(err) => match err.chain().find_map(|e| e.downcast_ref::<ErrorCode>()) {
+            // The Spectrum finds these as it's typed in, like a string for a number
+            None | Some(ErrorCode::Nonsense) => Ok(None),
+            Some(_) => Err(err),
+        },
+    }
+}
+
+/// Prints the prompt so far, then reads a variable from the console, asking again until
+/// what's typed makes sense.
+fn read(ident: &Ident, line: bool, prompt: &mut String, state: &mut State) -> Result<()> {
+    loop {
+        if !prompt.is_empty() {
+            state.print(&format!("{}\n", prompt))?;
+        }
+        let typed = state.console.read_line().ok_or(ErrorCode::StopInInput)?;
+        match typed_value(&typed, ident, line, state)? {
+            Some(Typed::Number(value)) => state.set_var(ident, value)?,
+            Some(Typed::String(value)) => state.set_string(ident, value)?,
+            None => continue,
+        }
+        prompt.clear();
+        return Ok(());
+    }
+}
+
+/// Runs INPUT. Its prompts are printed like PRINT, up to each variable, which is then read
+/// on a line of its own.
+pub(super) fn execute_input(items: &[InputItem], state: &mut State) -> Result<()> {
+    let mut prompt = String::new();
+    let mut separator = None;
+    for item in items {
+        match item {
+            InputItem::Separator(c) => separator = Some(*c),
+            InputItem::Prompt(expr) => {
+                match separator.take() {
+                    Some(',') => prompt.push(' '),
+                    Some('\'') => prompt.push('\n'),
+                    _ => {}
+                }
+                prompt.push_str(&expr.eval(state)?.to_string());
+            }
+            InputItem::At(line, column) => {
+                separator = None;
+                let (line, column) = (line.eval_to_int(state)?, column.eval_to_int(state)?);
+                let at = (u8::try_from(line).ok().filter(|&line| line < 24), u8::try_from(column).ok().filter(|&column| column < 32));
+                let (Some(line), Some(column)) = at else {
+                    return Err(ErrorCode::IntegerOutOfRange.with(format!("AT {},{}", line, column)));
+                };
+                state.print(&prompt)?;
+                prompt.clear();
+                state.console.input_at(line, column);
+            }
+            InputItem::Var(ident) => {
+                separator = None;
+                read(ident, false, &mut prompt, state)?;
+            }
+            InputItem::Line(ident) => {
+                separator = None;
+                read(ident, true, &mut prompt, state)?;
+            }
+        }
+    }
+    if !prompt.is_empty() {
+        state.print(&format!("{}\n", prompt))?;
+    }
+    Ok(())
+}
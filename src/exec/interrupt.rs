@@ -0,0 +1,13 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static BREAK: AtomicBool = AtomicBool::new(false);
+
+/// Stops the running program before its next statement, like pressing BREAK.
+/// Only sets a flag, so is safe to call from a Ctrl-C handler.
+pub fn request_break() {
+    BREAK.store(true, Ordering::SeqCst);
+}
+
+pub(crate) fn take_break() -> bool {
+    BREAK.swap(false, Ordering::SeqCst)
+}
@@ -0,0 +1,103 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{ensure, Result};
+
+use super::vars::{string_size, variable_size};
+use super::{ErrorCode, Vars};
+use crate::parser::Ident;
+
+/// Caps on what a program can do, for running programs that can't be trusted. Going over one
+/// stops the program with `ErrorCode::LimitExceeded`. `None` is no limit, the default.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Limits {
+    /// Statements run, counting each time round a loop. REM statements aren't counted.
+    pub statements: Option<u64>,
+    /// Time since the first statement ran.
+    pub timeout: Option<Duration>,
+    /// Variables defined at once.
+    pub variables: Option<usize>,
+    /// Bytes the variables would take up in the Spectrum's memory.
+    pub memory: Option<usize>,
+    /// Bytes printed.
+    pub output: Option<usize>,
+}
+
+/// How much of its limits a program has used. It's kept with the state, so counts go on
+/// across CONTINUE and RUN.
+#[derive(Debug, Clone, Default)]
+pub struct Usage {
+    pub statements: u64,
+    pub started: Option<Instant>,
+    pub output: usize,
+}
+
+fn exceeded(what: String) -> anyhow::Error {
+    ErrorCode::LimitExceeded.with(what)
+}
+
+impl Limits {
+    /// Counts a statement about to run, checking it's within the limits.
+    pub(super) fn statement(&self, usage: &mut Usage) -> Result<()> {
+        usage.statements += 1;
+        if let Some(max) = self.statements {
+            ensure!(usage.statements <= max, exceeded(format!("More than {} statements", max)));
+        }
+        if let Some(timeout) = self.timeout {
+            let started = *usage.started.get_or_insert_with(Instant::now);
+            ensure!(
+                started.elapsed() <= timeout,
+                exceeded(format!("Ran for more than {:?}", timeout))
+            );
+        }
+        Ok(())
+    }
+
+    /// Checks there's room for a new number variable, as well as `vars`.
+    pub(super) fn new_variable(&self, name: &Ident, vars: &Vars) -> Result<()> {
+        self.check_variables(name, vars)?;
+        self.check_memory(name, 0, variable_size(vars.name(name.symbol())), vars)
+    }
+
+    /// Checks there's room to set a string variable to `value`, in place of what it was, if
+    /// it's already defined.
+    pub(super) fn set_string(&self, name: &Ident, value: &str, vars: &Vars) -> Result<()> {
+        let old = match vars.get_string(name.symbol()) {
+            Some(old) => string_size(old),
+            None => {
+                self.check_variables(name, vars)?;
+                0
+            }
+        };
+        self.check_memory(name, old, string_size(value), vars)
+    }
+
+    fn check_variables(&self, name: &Ident, vars: &Vars) -> Result<()> {
+        if let Some(max) = self.variables {
+            ensure!(vars.len() < max, exceeded(format!("More than {} variables, defining {}", max, name)));
+        }
+        Ok(())
+    }
+
+    /// Checks the variables fit in memory once `old` bytes of them are replaced by `new`.
+    fn check_memory(&self, name: &Ident, old: usize, new: usize, vars: &Vars) -> Result<()> {
+        if let Some(max) = self.memory {
+            ensure!(
+                vars.size() - old + new <= max,
+                exceeded(format!("Variables take more than {} bytes, defining {}", max, name))
+            );
+        }
+        Ok(())
+    }
+
+    /// Counts `text` about to be printed, checking it's within the limits.
+    pub(super) fn output(&self, usage: &mut Usage, text: &str) -> Result<()> {
+        if let Some(max) = self.output {
+            ensure!(
+                usage.output + text.len() <= max,
+                exceeded(format!("Printed more than {} bytes", max))
+            );
+        }
+        usage.output += text.len();
+        Ok(())
+    }
+}
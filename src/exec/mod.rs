@@ -1,7 +1,27 @@
+mod compile;
 mod execute;
+mod execute_tests;
+mod input;
+mod interrupt;
+mod limits;
+mod optimize;
+mod optimize_tests;
+mod report;
 mod state;
+mod tape;
+#[cfg(test)]
+mod test_fixture;
 mod value;
+mod vars;
+mod vm;
+mod vm_tests;
 
-pub use self::execute::execute;
-pub use self::state::State;
-pub use self::value::Value;
\ No newline at end of file
+pub use self::execute::{execute_immediate, run, step};
+pub(crate) use self::interrupt::take_break;
+pub use self::interrupt::request_break;
+pub use self::limits::{Limits, Usage};
+pub use self::report::{ErrorCode, Report};
+pub(crate) use self::state::State;
+pub use self::state::{Dialect, LoopState, Memory};
+pub use self::value::Value;
+pub use self::vars::Vars;
@@ -0,0 +1,128 @@
+use crate::parser::{Expr, InputItem, Instr, Line, TapeData};
+
+/// The value of an arithmetic operation on two numbers, if it can be worked out without
+/// running the program. Ones that fail, like dividing by 0, are left to fail when they run.
+fn arithmetic(expr: &Expr, a: i64, b: i64) -> Option<i64> {
+    match expr {
+        Expr::Add(..) => a.checked_add(b),
+        Expr::Sub(..) => a.checked_sub(b),
+        Expr::Mul(..) => a.checked_mul(b),
+        Expr::Div(..) => a.checked_div(b),
+        _ => None,
+    }
+}
+
+/// Whether an expression is always a number, rather than a string or a comparison.
+fn numeric(expr: &Expr) -> bool {
+    matches!(
+        expr,
+        Expr::Ident(_)
+            | Expr::Int(_)
+            | Expr::Add(..)
+            | Expr::Sub(..)
+            | Expr::Mul(..)
+            | Expr::Div(..)
+            | Expr::Err
+            | Expr::Erl
+    )
+}
+
+/// Folds the parts of an expression made of numbers only into the number they come to, and
+/// drops adding 0 and multiplying or dividing by 1. Comparisons stay comparisons, as they
+/// aren't numbers when printed.
+pub(super) fn fold<'a>(expr: &Expr<'a>) -> Expr<'a> {
+    let (a, b) = match expr {
+        Expr::Ident(_) | Expr::Int(_) | Expr::String(_) | Expr::Err | Expr::Erl => return expr.clone(),
+        Expr::Add(a, b)
+        | Expr::Sub(a, b)
+        | Expr::Mul(a, b)
+        | Expr::Div(a, b)
+        | Expr::Gt(a, b)
+        | Expr::Lt(a, b)
+        | Expr::Eq(a, b)
+        | Expr::Ne(a, b)
+        | Expr::Ge(a, b)
+        | Expr::Le(a, b) => (fold(a), fold(b)),
+    };
+    if let (Expr::Int(a), Expr::Int(b)) = (&a, &b) {
+        if let Some(value) = arithmetic(expr, *a, *b) {
+            return Expr::Int(value);
+        }
+    }
+    match (expr, &a, &b) {
+        (Expr::Add(..), Expr::Int(0), _) | (Expr::Mul(..), Expr::Int(1), _) if numeric(&b) => return b,
+        (Expr::Add(..) | Expr::Sub(..), _, Expr::Int(0)) | (Expr::Mul(..) | Expr::Div(..), _, Expr::Int(1))
+            if numeric(&a) =>
+        {
+            return a
+        }
+        _ => {}
+    }
+    let (a, b) = (Box::new(a), Box::new(b));
+    match expr {
+        Expr::Add(..) => Expr::Add(a, b),
+        Expr::Sub(..) => Expr::Sub(a, b),
+        Expr::Mul(..) => Expr::Mul(a, b),
+        Expr::Div(..) => Expr::Div(a, b),
+        Expr::Gt(..) => Expr::Gt(a, b),
+        Expr::Lt(..) => Expr::Lt(a, b),
+        Expr::Eq(..) => Expr::Eq(a, b),
+        Expr::Ne(..) => Expr::Ne(a, b),
+        Expr::Ge(..) => Expr::Ge(a, b),
+        Expr::Le(..) => Expr::Le(a, b),
+        Expr::Ident(_) | Expr::Int(_) | Expr::String(_) | Expr::Err | Expr::Erl => {
+            unreachable!("not an operation")
+        }
+    }
+}
+
+/// Folds the expressions in a statement, including the bounds and step of a FOR loop.
+fn optimize_instr<'a>(instr: &Instr<'a>) -> Instr<'a> {
+    match instr {
+        Instr::Print(first, rest, last) => Instr::Print(
+            first.as_ref().map(fold),
+            rest.iter().map(|(separator, expr)| (*separator, fold(expr))).collect(),
+            *last,
+        ),
+        Instr::Assign(name, expr) => Instr::Assign(name.clone(), fold(expr)),
+        Instr::Input(items) => Instr::Input(
+            items
+                .iter()
+                .map(|item| match item {
+                    InputItem::Prompt(expr) => InputItem::Prompt(fold(expr)),
+                    InputItem::At(line, column) => InputItem::At(fold(line), fold(column)),
+                    item => item.clone(),
+                })
+                .collect(),
+        ),
+        Instr::IfThen(condition, then) => Instr::IfThen(fold(condition), Box::new(optimize_instr(then))),
+        Instr::Multi(instrs) => {
+            // REM takes up the rest of its line, so leaving it out doesn't move any statement
+            let instrs = instrs.iter().filter(|instr| !matches!(instr, Instr::Rem(_)));
+            Instr::Multi(instrs.map(optimize_instr).collect())
+        }
+        Instr::Rem(_) => Instr::Multi(vec![]),
+        Instr::For(name, start, end, step) => Instr::For(name.clone(), fold(start), fold(end), fold(step)),
+        Instr::Tape(command, name, TapeData::Code(start, length)) => Instr::Tape(
+            *command,
+            fold(name),
+            TapeData::Code(start.as_ref().map(fold), length.as_ref().map(fold)),
+        ),
+        Instr::Tape(command, name, data) => Instr::Tape(*command, fold(name), data.clone()),
+        _ => instr.clone(),
+    }
+}
+
+/// A copy of the program to compile, with its constant expressions worked out and its REM
+/// statements left out. Lines and statements keep their indices, so errors are reported where
+/// they are in the listing, and a jump to a REM lands, through `Program::starts`, on whatever
+/// comes after it.
+pub(super) fn optimize<'a>(lines: &[Line<'a>]) -> Vec<Line<'a>> {
+    lines
+        .iter()
+        .map(|line| Line {
+            number: line.number,
+            instr: optimize_instr(&line.instr),
+        })
+        .collect()
+}
@@ -0,0 +1,41 @@
+#[cfg(test)]
+mod tests {
+    use crate::exec::optimize::{fold, optimize};
+    use crate::parser::{parse_file, Expr, Instr};
+
+    fn folded(expr: &str) -> String {
+        let (rest, expr) = Expr::parse(expr).unwrap();
+        assert_eq!(rest, "");
+        fold(&expr).to_string()
+    }
+
+    #[test]
+    fn test_fold() {
+        assert_eq!(folded("1+2*3-4"), "3");
+        assert_eq!(folded("a*(2+3)"), "a*5");
+        assert_eq!(folded("(a+0)*1-0"), "a");
+        assert_eq!(folded("0+a/1"), "a");
+        assert_eq!(folded("a+2*3<10-1"), "a+6<9");
+        // Ones that fail when run are left alone
+        assert_eq!(folded("1/0"), "1/0");
+        assert_eq!(folded("9223372036854775807+1"), "9223372036854775807+1");
+        // Adding 0 to a string or a comparison is an error, not the same thing
+        assert_eq!(folded("\"x\"+0"), "\"x\"+0");
+        assert_eq!(folded("(1<2)*1"), "(1<2)*1");
+    }
+
+    #[test]
+    fn test_optimize() {
+        let program = "10 REM start\n20 FOR i=1+1 TO 2*5 STEP 3-1: PRINT i: REM loop\n30 IF 1=1 THEN REM\n40 NEXT i";
+        let lines = optimize(&parse_file(program, true).unwrap());
+        let numbers: Vec<_> = lines.iter().map(|line| line.number).collect();
+        assert_eq!(numbers, [10, 20, 30, 40]);
+        assert_eq!(lines[0].instr, Instr::Multi(vec![]));
+        assert_eq!(lines[1].instr.to_string(), "FOR i=2 TO 10 STEP 2: PRINT i");
+        assert_eq!(lines[2].instr, Instr::IfThen(parse_expr("1=1"), Box::new(Instr::Multi(vec![]))));
+    }
+
+    fn parse_expr(expr: &str) -> Expr<'_> {
+        Expr::parse(expr).unwrap().1
+    }
+}
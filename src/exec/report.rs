@@ -0,0 +1,138 @@
+use std::fmt::{Display, Formatter, Result};
+
+/// The Spectrum's report codes, for the ways a program can stop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    NextWithoutFor,
+    VariableNotFound,
+    NumberTooBig,
+    ReturnWithoutGosub,
+    Stop,
+    IntegerOutOfRange,
+    Nonsense,
+    Break,
+    InvalidFileName,
+    StopInInput,
+    ForWithoutNext,
+    StatementLost,
+    TapeLoadingError,
+    /// Not one of the Spectrum's: the program went over one of its `Limits`.
+    LimitExceeded,
+}
+
+impl ErrorCode {
+    pub fn code(self) -> char {
+        match self {
+            ErrorCode::NextWithoutFor => '1',
+            ErrorCode::VariableNotFound => '2',
+            ErrorCode::NumberTooBig => '6',
+            ErrorCode::ReturnWithoutGosub => '7',
+            ErrorCode::Stop => '9',
+            ErrorCode::IntegerOutOfRange => 'B',
+            ErrorCode::Nonsense => 'C',
+            ErrorCode::Break => 'D',
+            ErrorCode::InvalidFileName => 'F',
+            ErrorCode::StopInInput => 'H',
+            ErrorCode::ForWithoutNext => 'I',
+            ErrorCode::StatementLost => 'N',
+            ErrorCode::TapeLoadingError => 'R',
+            ErrorCode::LimitExceeded => 'X',
+        }
+    }
+
+    pub fn message(self) -> &'static str {
+        match self {
+            ErrorCode::NextWithoutFor => "NEXT without FOR",
+            ErrorCode::VariableNotFound => "Variable not found",
+            ErrorCode::NumberTooBig => "Number too big",
+            ErrorCode::ReturnWithoutGosub => "RETURN without GO SUB",
+            ErrorCode::Stop => "STOP statement",
+            ErrorCode::IntegerOutOfRange => "Integer out of range",
+            ErrorCode::Nonsense => "Nonsense in BASIC",
+            ErrorCode::Break => "BREAK - CONT repeats",
+            ErrorCode::InvalidFileName => "Invalid file name",
+            ErrorCode::StopInInput => "STOP in INPUT",
+            ErrorCode::ForWithoutNext => "FOR without NEXT",
+            ErrorCode::StatementLost => "Statement lost",
+            ErrorCode::TapeLoadingError => "Tape loading error",
+            ErrorCode::LimitExceeded => "Limit exceeded",
+        }
+    }
+
+    /// An error for a statement stopping with this report, with more detail than the Spectrum gives.
+    pub fn with(self, detail: impl Display) -> anyhow::Error {
+        anyhow::Error::new(self).context(detail.to_string())
+    }
+
+    /// Whether CONTINUE goes on to the next statement, rather than repeating the one that stopped.
+    pub fn continues_after(self) -> bool {
+        self == ErrorCode::Stop
+    }
+
+    /// The code as a number, for `ERR`: 1 to 9, then A is 10, B is 11 and so on.
+    pub fn number(self) -> u32 {
+        self.code().to_digit(36).expect("codes are digits or letters")
+    }
+
+    /// Whether ON ERROR GO TO can handle it. Stopping the program, by STOP, BREAK or going
+    /// over a limit, can't be.
+    pub fn trappable(self) -> bool {
+        !matches!(
+            self,
+            ErrorCode::Stop | ErrorCode::Break | ErrorCode::StopInInput | ErrorCode::LimitExceeded
+        )
+    }
+}
+
+impl Display for ErrorCode {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{} {}", self.code(), self.message())
+    }
+}
+
+impl std::error::Error for ErrorCode {}
+
+/// Why and where a program stopped, e.g. `2 Variable not found, 20:1`. Immediate
+/// commands are reported as line 0.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Report {
+    pub code: ErrorCode,
+    pub line: usize,
+    pub statement: usize, // 1-based, like the Spectrum
+    pub detail: Option<String>,
+}
+
+impl Report {
+    /// Reports an error raised by a statement. Errors without an `ErrorCode` are ones the
+    /// Spectrum would have rejected when the line was typed in, so are nonsense in BASIC.
+    pub(crate) fn new(err: anyhow::Error, line: usize, statement: usize) -> Self {
+        let code = err
+            .chain()
+            .find_map(|e| e.downcast_ref::<ErrorCode>())
+            .copied();
+        // A bare code has nothing to add, but one from `ErrorCode::with` has its detail
+        let detail = match err.chain().count() {
+            1 if code.is_some() => None,
+            _ => Some(err.to_string()),
+        };
+        let code = code.unwrap_or(ErrorCode::Nonsense);
+        Report {
+            code,
+            line,
+            statement,
+            detail,
+        }
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}, {}:{}", self.code, self.line, self.statement)?;
+        match &self.detail {
+            Some(detail) => write!(f, " ({})", detail),
+            None => Ok(()),
+        }
+    }
+}
+
+impl std::error::Error for Report {}
@@ -1,28 +1,217 @@
-use anyhow::{anyhow, Result};
-use std::collections::HashMap;
+use anyhow::Result;
 
-use crate::parser::LowerCase;
+use super::{ErrorCode, Limits, Report, Usage, Vars};
+use crate::console::{Console, Terminal};
+use crate::parser::{find_line, Ident, Line};
+use crate::source::Sources;
+use crate::tape::Tape;
+use crate::trace::Tracer;
 
-#[derive(Debug, Default)]
-pub struct State<'a> {
-    pub vars: HashMap<LowerCase<'a>, i64>,
-    pub pc: usize,
-    pub loop_stack: Vec<LoopState<'a>>,
+#[derive(Debug)]
+pub(crate) struct State<'a> {
+    pub(crate) vars: Vars,
+    pub(crate) pc: usize,   // Index of the current line
+    pub(crate) stmt: usize, // Index of the current statement within the line
+    pub(crate) gosub_stack: Vec<(usize, usize)>, // (pc, stmt) to return to
+    pub(crate) cont: Option<(usize, usize)>,     // (pc, stmt) for CONTINUE to pick up at
+    pub(crate) tape: Option<Tape>,
+    pub(crate) memory: Memory,
+    pub(crate) loaded: Option<Vec<Line<'a>>>, // A program LOADed, MERGEd or RENUMBERed, to replace the running one
+    pub(crate) sources: Option<&'a Sources>,  // Where LOAD and MERGE keep the listings they read
+    pub(crate) console: Box<dyn Console>,
+    pub(crate) limits: Limits,
+    pub(crate) usage: Usage,
+    pub(crate) tracer: Option<Box<dyn Tracer>>,
+    pub(crate) dialect: Dialect,
+    pub(crate) on_error: Option<usize>,          // The line ON ERROR GO TO jumps to
+    pub(crate) handling: Option<(usize, usize)>, // (pc, stmt) of the error being handled, for RESUME
+    pub(crate) trapped: Option<Report>,          // The last error ON ERROR GO TO trapped, for ERR and ERL
 }
 
-#[derive(Debug)]
-pub struct LoopState<'a> {
-    pub start_line: usize,
-    pub var_name: LowerCase<'a>,
-    pub end_value: i64,
+impl Default for State<'_> {
+    fn default() -> Self {
+        State {
+            vars: Vars::default(),
+            pc: 0,
+            stmt: 0,
+            gosub_stack: vec![],
+            cont: None,
+            tape: None,
+            memory: Memory::default(),
+            loaded: None,
+            sources: None,
+            console: Box::new(Terminal),
+            limits: Limits::default(),
+            usage: Usage::default(),
+            tracer: None,
+            dialect: Dialect::default(),
+            on_error: None,
+            handling: None,
+            trapped: None,
+        }
+    }
+}
+
+/// Which BASIC the interpreter accepts. The extended dialect adds statements the Spectrum
+/// doesn't have: `ON ERROR GO TO`, with `ERR` and `ERL` to tell what went wrong, and `RESUME`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    #[default]
+    Spectrum,
+    Extended,
+}
+
+/// The Spectrum's 64K address space, which CODE is saved from and loaded into.
+#[derive(Clone)]
+pub struct Memory(pub Box<[u8]>);
+
+impl Default for Memory {
+    fn default() -> Self {
+        Memory(vec![0; 0x10000].into_boxed_slice())
+    }
+}
+
+impl std::fmt::Debug for Memory {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Memory({} bytes)", self.0.len())
+    }
+}
+
+/// A FOR loop. Like on the Spectrum, it's kept with its control variable, so a NEXT anywhere
+/// loops back to the last FOR for that variable.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoopState {
+    pub limit: i64,
     pub step: i64, // TODO: Floats
+    /// The indices of the FOR statement's line and statement. The loop goes back to the
+    /// statement after it.
+    pub pc: usize,
+    pub stmt: usize,
 }
 
-impl State<'_> {
-    pub fn get_var(&self, ident: &LowerCase) -> Result<i64> {
+impl LoopState {
+    /// Whether the loop runs again, with its control variable at `value`. A step of 0 counts
+    /// up, so it loops forever unless the start is past the limit.
+    pub fn running(&self, value: i64) -> bool {
+        match self.step >= 0 {
+            true => value <= self.limit,
+            false => value >= self.limit,
+        }
+    }
+}
+
+impl<'a> State<'a> {
+    pub fn jump(&mut self, pc: usize, stmt: usize) {
+        self.pc = pc;
+        self.stmt = stmt;
+    }
+
+    /// Moves to where the program last stopped, so that running it carries on from there.
+    pub fn resume(&mut self) -> Result<()> {
+        let (pc, stmt) = self.cont.ok_or(ErrorCode::StatementLost)?;
+        self.jump(pc, stmt);
+        Ok(())
+    }
+
+    /// Clears the variables, with their FOR loops, and the GO SUB stack, like CLEAR.
+    pub fn clear(&mut self) {
+        self.vars.clear();
+        self.gosub_stack.clear();
+    }
+
+    /// Sets a variable, defining it if it's new.
+    pub fn set_var(&mut self, ident: &Ident, value: i64) -> Result<()> {
+        if ident.is_string() {
+            return Err(ErrorCode::Nonsense.with(format!("{} is a string variable", ident)));
+        }
+        if !self.vars.contains(ident.symbol()) {
+            self.limits.new_variable(ident, &self.vars)?;
+        }
+        if let Some(tracer) = &mut self.tracer {
+            tracer.assign(ident.name, value);
+        }
+        self.vars.set(ident.symbol(), value);
+        Ok(())
+    }
+
+    /// Defines the variables saved with a program, on tape or in a snapshot. They count
+    /// towards the limits the same as ones the program defines.
+    pub fn load_vars<'n>(&mut self, vars: impl IntoIterator<Item = (&'n str, i64)>) -> Result<()> {
+        for (name, value) in vars {
+            let mut ident = Ident::new(name);
+            self.vars.intern_ident(&mut ident);
+            if !self.vars.contains(ident.symbol()) {
+                self.limits.new_variable(&ident, &self.vars)?;
+            }
+            self.vars.set(ident.symbol(), value);
+        }
+        Ok(())
+    }
+
+    /// Sets a string variable, defining it if it's new.
+    pub fn set_string(&mut self, ident: &Ident, value: String) -> Result<()> {
+        self.limits.set_string(ident, &value, &self.vars)?;
+        if let Some(tracer) = &mut self.tracer {
+            tracer.assign_string(ident.name, &value);
+        }
+        self.vars.set_string(ident.symbol(), value);
+        Ok(())
+    }
+
+    /// Prints to the console, like PRINT.
+    pub fn print(&mut self, text: &str) -> Result<()> {
+        self.limits.output(&mut self.usage, text)?;
+        self.console.print(text);
+        Ok(())
+    }
+
+    pub fn get_var(&self, ident: &Ident) -> Result<i64> {
+        self.vars.get(ident.symbol()).ok_or_else(|| match ident.is_string() {
+            true => ErrorCode::Nonsense.with(format!("{} is a string variable", ident)),
+            false => ErrorCode::VariableNotFound.with(ident),
+        })
+    }
+
+    pub fn get_string(&self, ident: &Ident) -> Result<&str> {
         self.vars
-            .get(ident)
-            .ok_or(anyhow!("NameError: {}", ident))
-            .copied()
+            .get_string(ident.symbol())
+            .ok_or_else(|| ErrorCode::VariableNotFound.with(ident))
+    }
+
+    /// Sets a FOR loop's control variable to `start`, replacing any loop it had. Returns
+    /// whether the loop runs at all, or should skip to its NEXT.
+    pub fn start_loop(&mut self, ident: &Ident, start: i64, for_loop: LoopState) -> Result<bool> {
+        self.set_var(ident, start)?;
+        self.vars.set_loop(ident.symbol(), for_loop);
+        Ok(for_loop.running(start))
+    }
+
+    /// Steps the FOR loop of `ident`, like NEXT. Returns the indices of the line and statement
+    /// to loop back to, or `None` when the loop has finished.
+    pub fn next(&mut self, ident: &Ident) -> Result<Option<(usize, usize)>> {
+        let value = self.get_var(ident)?;
+        let for_loop = self
+            .vars
+            .for_loop(ident.symbol())
+            .ok_or_else(|| ErrorCode::NextWithoutFor.with(format!("{} isn't the variable of a FOR loop", ident)))?;
+        let value = value.checked_add(for_loop.step).ok_or(ErrorCode::NumberTooBig)?;
+        self.set_var(ident, value)?;
+        Ok(for_loop.running(value).then_some((for_loop.pc, for_loop.stmt + 1)))
+    }
+
+    /// Jumps to the ON ERROR GO TO line, if there is one, for an error the program can handle,
+    /// with `ERR` and `ERL` giving its report code and line. An error while handling another,
+    /// until RESUME or ON ERROR GO TO, isn't trapped. Returns whether it was.
+    pub fn trap(&mut self, report: &Report, pc: usize, stmt: usize, lines: &[Line]) -> bool {
+        let Some(number) = self.on_error else {
+            return false;
+        };
+        if self.handling.is_some() || !report.code.trappable() {
+            return false;
+        }
+        self.trapped = Some(report.clone());
+        self.handling = Some((pc, stmt));
+        self.jump(find_line(lines, number), 0);
+        true
     }
 }
@@ -0,0 +1,166 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use anyhow::{anyhow, ensure, Result};
+
+use super::{ErrorCode, State, Value};
+use crate::parser::{find_line, parse_file_recovering, Expr, Line, TapeCommand, TapeData};
+use crate::tape::{program_file, FileType, Tape, TapeFile, TapeProgram};
+
+/// SCREEN$ is the display file and its attributes.
+const SCREEN: (usize, usize) = (16384, 6912);
+/// What the header of saved CODE holds in place of a program's length.
+const CODE_PARAM2: u16 = 32768;
+
+fn loading_error(err: impl Display) -> anyhow::Error {
+    ErrorCode::TapeLoadingError.with(err)
+}
+
+fn file_name(name: &Expr, state: &State, command: TapeCommand) -> Result<String> {
+    let name = match name.eval(state)? {
+        Value::String(name) => name,
+        other => return Err(anyhow!("Expected a file name, found: {}", other)),
+    };
+    let invalid = |why| ErrorCode::InvalidFileName.with(format!("{:?} {}", name, why));
+    ensure!(name.len() <= 10 && name.is_ascii(), invalid("is more than 10 characters"));
+    if command == TapeCommand::Save {
+        ensure!(!name.is_empty(), invalid("is empty"));
+        // Names are file names when the tape is a directory
+        ensure!(!name.contains(['/', '\\']), invalid("contains a path separator"));
+    }
+    Ok(name.to_string())
+}
+
+fn tape<'s>(state: &'s mut State) -> Result<&'s mut Tape> {
+    state
+        .tape
+        .as_mut()
+        .ok_or_else(|| loading_error("There is no tape, give one with --tape"))
+}
+
+/// The bytes CODE refers to, from the start and length given or else `default`.
+fn code_range(
+    start: Option<&Expr>,
+    length: Option<&Expr>,
+    default: (usize, Option<usize>),
+    state: &State,
+) -> Result<(usize, Option<usize>)> {
+    let eval = |expr: &Expr| -> Result<usize> {
+        let value = expr.eval_to_int(state)?;
+        ensure!((0..=0xFFFF).contains(&value), ErrorCode::IntegerOutOfRange.with(value));
+        Ok(value as usize)
+    };
+    let start = start.map(eval).transpose()?.unwrap_or(default.0);
+    let length = length.map(eval).transpose()?.or(default.1);
+    if let Some(length) = length {
+        ensure!(
+            start + length <= 0x10000,
+            ErrorCode::IntegerOutOfRange.with(format!("CODE {},{} is past the end of memory", start, length))
+        );
+    }
+    Ok((start, length))
+}
+
+/// Parses a program from tape, keeping its listing in `state.sources` for as long as the
+/// program could run, and interning its names.
+fn parse_listing<'a>(listing: &str, state: &mut State<'a>) -> Result<Vec<Line<'a>>> {
+    let sources = state
+        .sources
+        .ok_or_else(|| loading_error("There is nowhere to keep a loaded program"))?;
+    let mut lines = parse_file_recovering(sources.keep(listing), true).0;
+    state.vars.intern(&mut lines);
+    Ok(lines)
+}
+
+/// Replaces the program with one from tape, which runs from its auto-start line if it has one.
+fn load_program<'a>(state: &mut State<'a>, lines: Vec<Line<'a>>, autostart: Option<usize>) -> bool {
+    state.jump(autostart.map_or(lines.len(), |number| find_line(&lines, number)), 0);
+    state.loaded = Some(lines);
+    state.cont = None;
+    true
+}
+
+/// Runs SAVE, LOAD, VERIFY or MERGE. Returns whether it jumped, which loading a program does.
+pub(super) fn execute_tape<'a>(
+    command: TapeCommand,
+    name: &Expr,
+    data: &TapeData,
+    state: &mut State<'a>,
+    lines: &[Line<'a>],
+) -> Result<bool> {
+    let name = file_name(name, state, command)?;
+    let (start, length) = match data {
+        TapeData::Program(_) => (None, None),
+        TapeData::Code(start, length) => (start.as_ref(), length.as_ref()),
+        TapeData::Screen => (None, None),
+        TapeData::Data(array) => {
+            return Err(ErrorCode::Nonsense.with(format!("Arrays aren't supported: {}()", array)))
+        }
+    };
+    if let TapeData::Program(autostart) = data {
+        if command == TapeCommand::Save {
+            let file = program_file(&name, lines, *autostart, state.vars.iter())?;
+            tape(state)?.save(file).map_err(loading_error)?;
+            return Ok(false);
+        }
+        let file = tape(state)?
+            .find(&name, |file_type| file_type == FileType::Program)
+            .map_err(loading_error)?;
+        let program = TapeProgram::from_file(&file).map_err(loading_error)?;
+        let loaded = parse_listing(&program.listing, state)?;
+        let variables = program.variables.iter().map(|(name, value)| (name.as_str(), *value));
+        return match command {
+            TapeCommand::Verify => {
+                ensure!(loaded == lines, loading_error(format!("{} doesn't match the program", name)));
+                Ok(false)
+            }
+            TapeCommand::Merge => {
+                // Lines from tape replace any with the same number
+                let mut merged: BTreeMap<_, _> = lines.iter().map(|l| (l.number, l.clone())).collect();
+                merged.extend(loaded.into_iter().map(|l| (l.number, l)));
+                state.load_vars(variables)?;
+                // Loops and GO SUBs go back to lines that may have moved
+                state.vars.clear_loops();
+                state.gosub_stack.clear();
+                Ok(load_program(state, merged.into_values().collect(), None))
+            }
+            _ => {
+                state.clear();
+                state.load_vars(variables)?;
+                Ok(load_program(state, loaded, program.autostart))
+            }
+        };
+    }
+
+    if command == TapeCommand::Save {
+        let (start, length) = code_range(start, length, (SCREEN.0, Some(SCREEN.1)), state)?;
+        let length = length.expect("the parser requires a length to SAVE CODE");
+        let bytes = state.memory.0[start..start + length].to_vec();
+        let file = TapeFile::new(FileType::Code, &name, bytes, start as u16, CODE_PARAM2);
+        tape(state)?.save(file).map_err(loading_error)?;
+        return Ok(false);
+    }
+    let file = tape(state)?
+        .find(&name, |file_type| file_type == FileType::Code)
+        .map_err(loading_error)?;
+    let default = match data {
+        TapeData::Screen => (SCREEN.0, Some(SCREEN.1)),
+        _ => (file.header.param1 as usize, None),
+    };
+    let (start, length) = code_range(start, length, default, state)?;
+    let bytes = &file.data;
+    ensure!(
+        length.is_none_or(|length| bytes.len() <= length),
+        loading_error(format!("{} is {} bytes, more than {}", name, bytes.len(), length.unwrap_or(0)))
+    );
+    ensure!(
+        start + bytes.len() <= 0x10000,
+        ErrorCode::IntegerOutOfRange.with(format!("{} doesn't fit at {}", name, start))
+    );
+    let memory = &mut state.memory.0[start..start + bytes.len()];
+    match command {
+        TapeCommand::Verify => ensure!(memory == &bytes[..], loading_error(format!("{} doesn't match memory", name))),
+        _ => memory.copy_from_slice(bytes),
+    }
+    Ok(false)
+}
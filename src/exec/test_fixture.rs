@@ -0,0 +1,66 @@
+//! Runs programs for the tests both compiled and a statement at a time, checking that nothing
+//! about how they ended tells the two apart.
+use crate::console::Scripted;
+use crate::exec::{Dialect, Limits, LoopState};
+use crate::parser::parse_file_recovering;
+use crate::{Interpreter, Status};
+
+/// Everything about a program that could tell how it was run.
+#[derive(Debug, PartialEq)]
+struct Outcome {
+    status: Status,
+    output: String,
+    vars: Vec<(String, i64)>,
+    position: (usize, usize),
+    cont: Option<(usize, usize)>,
+    loops: Vec<(String, LoopState)>,
+    gosubs: Vec<(usize, usize)>,
+}
+
+fn outcome(interpreter: &Interpreter, status: Status, output: String) -> Outcome {
+    let state = interpreter.state();
+    let mut vars: Vec<_> = state.vars.iter().map(|(name, value)| (name.to_string(), value)).collect();
+    vars.sort();
+    Outcome {
+        status,
+        output,
+        vars,
+        position: (state.pc, state.stmt),
+        cont: state.cont,
+        loops: state.vars.loops().map(|(symbol, for_loop)| (state.vars.name(symbol).to_string(), for_loop)).collect(),
+        gosubs: state.gosub_stack.clone(),
+    }
+}
+
+/// Runs a program compiled, and a statement at a time, then CONTINUEs it if it stopped,
+/// checking both ways go the same. Returns what it printed, then how it first ended.
+pub(super) fn run_both(program: &str, input: &[&str], dialect: Dialect, limits: Limits) -> String {
+    let mut outcomes = vec![];
+    for compiled in [true, false] {
+        let console = Scripted::new(input.iter().copied());
+        let capture = console.capture();
+        let mut interpreter = Interpreter::new(parse_file_recovering(program, true).0);
+        interpreter.set_console(Box::new(console));
+        interpreter.set_dialect(dialect);
+        interpreter.set_limits(limits.clone());
+        let run = |interpreter: &mut Interpreter| match compiled {
+            true => interpreter.run(),
+            false => interpreter.run_until(|_| false),
+        };
+        let status = run(&mut interpreter);
+        let mut outcome = vec![self::outcome(&interpreter, status.clone(), capture.output())];
+        if let Status::Stopped(_) = status {
+            interpreter.state_mut().resume().unwrap();
+            let status = run(&mut interpreter);
+            outcome.push(self::outcome(&interpreter, status, capture.output()));
+        }
+        outcomes.push(outcome);
+    }
+    assert_eq!(outcomes[0], outcomes[1], "{}", program);
+    let first = outcomes.remove(0).remove(0);
+    let end = match first.status {
+        Status::Stopped(report) => report.to_string(),
+        status => format!("{:?}", status),
+    };
+    first.output + &end
+}
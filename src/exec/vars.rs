@@ -0,0 +1,165 @@
+use super::LoopState;
+use crate::parser::{Expr, Ident, Instr, Line, Symbol, Symbols};
+
+/// The values of the variables, indexed by the symbols their names are interned as, and the
+/// FOR loops of the ones that are control variables. String variables, like `a$`, have symbols
+/// of their own. A program's names are interned before it runs, so looking a variable up is
+/// indexing a vector.
+#[derive(Debug, Default, Clone)]
+pub struct Vars {
+    symbols: Symbols,
+    values: Vec<Option<i64>>,
+    strings: Vec<Option<String>>,
+    loops: Vec<Option<LoopState>>,
+    len: usize,
+    size: usize,
+}
+
+/// The bytes a number variable takes up in the Spectrum's variables area: its name, without
+/// spaces, and the 5-byte number.
+pub(super) fn variable_size(name: &str) -> usize {
+    name.len() + 5
+}
+
+/// The bytes a string variable takes up: its letter, a 2-byte length, and the text.
+pub(super) fn string_size(value: &str) -> usize {
+    value.len() + 3
+}
+
+impl Vars {
+    /// Interns the names of the variables a program uses, so they can be looked up by their
+    /// symbols. Names stay interned, even when the variables are cleared.
+    pub fn intern(&mut self, lines: &mut [Line]) {
+        for line in lines {
+            line.instr.intern(&mut self.symbols);
+        }
+        self.grow();
+    }
+
+    /// Interns the names a statement uses, like `intern`.
+    pub fn intern_instr(&mut self, instr: &mut Instr) {
+        instr.intern(&mut self.symbols);
+        self.grow();
+    }
+
+    /// Interns the names an expression uses, like `intern`.
+    pub fn intern_expr(&mut self, expr: &mut Expr) {
+        expr.intern(&mut self.symbols);
+        self.grow();
+    }
+
+    /// Interns a name on its own, for a variable defined from outside the program.
+    pub fn intern_ident(&mut self, ident: &mut Ident) {
+        ident.intern(&mut self.symbols);
+        self.grow();
+    }
+
+    /// Makes room for a value of every symbol.
+    fn grow(&mut self) {
+        let len = self.symbols.len();
+        self.values.resize(len, None);
+        self.strings.resize(len, None);
+        self.loops.resize(len, None);
+    }
+
+    /// The symbol of a name, if it's been interned.
+    pub fn symbol(&self, name: &str) -> Option<Symbol> {
+        self.symbols.find(name)
+    }
+
+    /// The name of a symbol as the Spectrum stores it.
+    pub fn name(&self, symbol: Symbol) -> &str {
+        self.symbols.name(symbol)
+    }
+
+    pub fn get(&self, symbol: Symbol) -> Option<i64> {
+        self.values.get(symbol.index()).copied().flatten()
+    }
+
+    pub fn contains(&self, symbol: Symbol) -> bool {
+        self.get(symbol).is_some()
+    }
+
+    /// Sets a variable, returning its old value, if it was defined.
+    pub fn set(&mut self, symbol: Symbol, value: i64) -> Option<i64> {
+        let old = self.values[symbol.index()].replace(value);
+        if old.is_none() {
+            self.len += 1;
+            self.size += variable_size(self.symbols.name(symbol));
+        }
+        old
+    }
+
+    pub fn get_string(&self, symbol: Symbol) -> Option<&str> {
+        self.strings.get(symbol.index())?.as_deref()
+    }
+
+    /// Sets a string variable, returning its old value, if it was defined.
+    pub fn set_string(&mut self, symbol: Symbol, value: String) -> Option<String> {
+        self.size += string_size(&value);
+        let old = self.strings[symbol.index()].replace(value);
+        match &old {
+            Some(old) => self.size -= string_size(old),
+            None => self.len += 1,
+        }
+        old
+    }
+
+    /// The FOR loop a variable is the control variable of.
+    pub fn for_loop(&self, symbol: Symbol) -> Option<LoopState> {
+        self.loops.get(symbol.index()).copied().flatten()
+    }
+
+    /// Makes a variable, which has to be defined, the control variable of a FOR loop.
+    pub fn set_loop(&mut self, symbol: Symbol, for_loop: LoopState) {
+        self.loops[symbol.index()] = Some(for_loop);
+    }
+
+    /// Forgets every FOR loop, leaving their control variables as plain variables.
+    pub fn clear_loops(&mut self) {
+        self.loops.fill(None);
+    }
+
+    /// The symbols of the control variables, and their FOR loops.
+    pub fn loops(&self) -> impl Iterator<Item = (Symbol, LoopState)> + '_ {
+        self.loops
+            .iter()
+            .enumerate()
+            .filter_map(|(i, for_loop)| Some((Symbol::new(i), (*for_loop)?)))
+    }
+
+    /// How many variables are defined.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// The bytes the variables would take up in the Spectrum's memory.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Clears the variables. Their names stay interned, for the program that uses them.
+    pub fn clear(&mut self) {
+        self.values.fill(None);
+        self.strings.fill(None);
+        self.loops.fill(None);
+        self.len = 0;
+        self.size = 0;
+    }
+
+    /// The number variables, by the names the Spectrum stores them as, and their values.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, i64)> + '_ {
+        self.values
+            .iter()
+            .zip(self.symbols.names())
+            .filter_map(|(value, name)| Some((name, (*value)?)))
+    }
+
+    /// The string variables and their values.
+    pub fn strings(&self) -> impl Iterator<Item = (&str, &str)> + '_ {
+        self.strings
+            .iter()
+            .zip(self.symbols.names())
+            .filter_map(|(value, name)| Some((name, value.as_deref()?)))
+    }
+}
@@ -0,0 +1,94 @@
+#[cfg(test)]
+mod tests {
+    use crate::exec::test_fixture::run_both;
+    use crate::exec::{Dialect, Limits};
+    use crate::parser::{parse_file_recovering, Instr};
+    use crate::{Interpreter, Status};
+
+    fn check(program: &str, input: &[&str], limits: Limits) {
+        run_both(program, input, Dialect::Spectrum, limits);
+    }
+
+    #[test]
+    fn test_same_as_interpreter() {
+        let programs = [
+            "10 LET a=7\n20 LET b=a*3-1\n30 PRINT a;b,a/2,b<a;\n40 PRINT \"x\",a=7\n50 PRINT",
+            "10 LET a=1\n20 PRINT a/0",
+            "10 LET a=1\n20 PRINT a+b",
+            "10 LET a=1+\"x\"",
+            "10 LET a=b+\"x\"",
+            "10 LET a=(1<2)+1",
+            "10 IF 1 THEN PRINT 1",
+            "10 IF \"x\" THEN PRINT 1",
+            "10 IF c THEN PRINT 1",
+            "10 LET a=1: IF a=1 THEN PRINT \"yes\": LET a=2: GO TO 30\n20 PRINT \"no\"\n30 PRINT a",
+            "10 LET t=0\n20 FOR i=1 TO 10 STEP 3\n30 LET t=t+i\n40 NEXT i\n50 PRINT t;i",
+            "10 FOR i=5 TO 1 STEP -2\n20 PRINT i\n30 NEXT i",
+            "10 LET n=0\n20 FOR i=1 TO 2 STEP 0\n30 LET n=n+1\n40 IF n<5 THEN NEXT i\n50 PRINT n;i",
+            "10 FOR i=5 TO 1\n20 PRINT i\n30 NEXT i: PRINT i",
+            "10 FOR i=5 TO 1: PRINT i",
+            "10 FOR i=1 TO 2\n20 FOR j=1 TO 3\n30 PRINT i;j\n40 NEXT i",
+            "10 LET i=1\n20 NEXT i",
+            "10 NEXT i",
+            "10 FOR i=1 TO 2\n20 NEXT j",
+            "10 LET a=1: IF a=2 THEN PRINT 1: PRINT 2\n20 IF a=1 THEN PRINT 3: PRINT 4",
+            "10 IF 1=1 THEN FOR i=1 TO 3: PRINT i: NEXT i: PRINT \"done\"",
+            "10 GO SUB 100: PRINT \"back\"\n20 IF 1=1 THEN GO SUB 100: PRINT \"skipped\"\n30 STOP\n100 PRINT \"sub\"\n110 RETURN",
+            "10 RETURN",
+            "10 GO TO 25\n20 PRINT 20\n30 PRINT 30\n40 GO TO 100",
+            "10 LET a=1\n20 STOP\n30 PRINT a: STOP: PRINT a+1",
+            "10 INPUT a\n20 INPUT \"b?\",b\n30 PRINT a*b\n40 INPUT c",
+            "10 INPUT a",
+            "10 LET a=1\n20 CLEAR\n30 PRINT a",
+            "10 LET a=1\n20 LIST 20",
+            "10 LET a=1\n20 this is nonsense\n30 PRINT a",
+            "10 LET a=1\n20 IF a=1 THEN CLEAR: LET b=2: PRINT b\n30 PRINT a",
+            "10 LET a=1\n20 IF a<3 THEN LET a=a+1: RUN 20\n30 PRINT a",
+            "10 LET A=1\n20 LET a=A+1\n30 PRINT a;A",
+            "10 REM start\n20 LET a=2*3+1: PRINT a*1+0,a/1-0: REM end\n30 PRINT a+0",
+            "10 FOR i=1+1 TO 2*3 STEP 4/2: PRINT i: REM loop\n20 NEXT i\n30 PRINT \"x\"+0",
+            "10 PRINT (1<2)*1",
+            "10 PRINT 2*(3/0)",
+            "10 LET my total 2=5\n20 LET My Total2=mytotal 2+1\n30 PRINT my total 2",
+            "10 LET t=1\n20 FOR i=1 TO 3: LET T=t*2: INPUT n: LET t=t+n: NEXT I\n30 PRINT t;i",
+            "10 LET a=9223372036854775807\n20 LET b=a+1",
+            "10 LET a=0-9223372036854775807\n20 LET b=a-2",
+            "10 LET a=4294967296\n20 PRINT a*a",
+            "10 LET a=9223372036854775807/-1\n20 LET b=(0-9223372036854775807-1)/(0-1)",
+            "10 INPUT \"a? \";a;\" b$? \";b$\n20 LET c$=b$: PRINT a;c$\n30 IF c$ THEN PRINT 1\n40 PRINT c$+1",
+            "10 LET a$=1",
+            "10 GO SUB 30: RENUMBER 100,5: LIST\n20 STOP\n30 RETURN",
+            "10 RENUMBER 9990,5\n20 PRINT 1",
+        ];
+        for program in programs {
+            check(program, &["6", "7"], Limits::default());
+        }
+    }
+
+    #[test]
+    fn test_limits() {
+        let forever = "10 LET a=a+1\n20 PRINT a\n30 GO TO 10";
+        let program = "10 LET a=0\n20 LET a=a+1\n30 PRINT a\n40 GO TO 20";
+        for limits in [
+            Limits { statements: Some(50), ..Limits::default() },
+            Limits { variables: Some(0), ..Limits::default() },
+            Limits { memory: Some(5), ..Limits::default() },
+            Limits { output: Some(20), ..Limits::default() },
+        ] {
+            check(forever, &[], limits.clone());
+            check(program, &[], limits);
+        }
+        // REM isn't counted as a statement run, compiled or not
+        let remarks = "10 REM loop\n20 LET a=a+1: REM count\n30 GO TO 10";
+        check(remarks, &[], Limits { statements: Some(50), ..Limits::default() });
+    }
+
+    #[test]
+    fn test_continue_into_compiled() {
+        // CONTINUE from the interpreter runs the rest of the program compiled
+        let mut interpreter = Interpreter::new(parse_file_recovering("10 LET a=1\n20 STOP\n30 LET a=a+1", true).0);
+        assert!(matches!(interpreter.run_until(|_| false), Status::Stopped(_)));
+        interpreter.execute(&Instr::Continue).unwrap();
+        assert_eq!(interpreter.var("a"), Some(2));
+    }
+}
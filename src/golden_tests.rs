@@ -0,0 +1,129 @@
+//! Runs each program in `programs/` and compares what it does with the files next to it:
+//! `name.in` holds the lines to INPUT, `name.tap` the tape to LOAD from, `name.out` the
+//! expected transcript (the output then the report the program stopped with) and,
+//! optionally, `name.png` the expected screen. The screen is drawn from the display file,
+//! which PRINT doesn't write to, so it's for programs that LOAD a SCREEN$ or CODE into it.
+//! Run with `UPDATE_GOLDEN=1` to write the expected files from what the programs do now.
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+    use std::path::{Path, PathBuf};
+
+    use crate::console::Scripted;
+    use crate::parser::parse_file;
+    use crate::screen::{self, HEIGHT, WIDTH};
+    use crate::tape::Tape;
+    use crate::{Interpreter, Status};
+
+    /// More than any of the programs need, so one that loops forever fails instead of hanging.
+    const MAX_STATEMENTS: usize = 100_000;
+
+    fn updating() -> bool {
+        std::env::var_os("UPDATE_GOLDEN").is_some()
+    }
+
+    /// Runs a program with its scripted input, returning its transcript and screen.
+    fn run(path: &Path, prefixed: bool) -> (String, Vec<u8>) {
+        let source = fs::read_to_string(path).expect("Failed to read program");
+        let input = fs::read_to_string(path.with_extension("in")).unwrap_or_default();
+        let console = Scripted::new(input.lines());
+        let capture = console.capture();
+        let mut interpreter = Interpreter::new(parse_file(&source, prefixed).expect("Failed to parse"));
+        interpreter.set_console(Box::new(console));
+        let tape = path.with_extension("tap");
+        if tape.exists() {
+            interpreter.set_tape(Tape::new(tape));
+        }
+        let mut statements = 0;
+        let report = match interpreter.run_until(|_| {
+            statements += 1;
+            statements >= MAX_STATEMENTS
+        }) {
+            Status::Finished => "0 OK".to_string(),
+            Status::Stopped(report) => report.to_string(),
+            Status::Running => format!("Still running after {} statements", MAX_STATEMENTS),
+        };
+        let mut transcript = capture.output();
+        if !transcript.is_empty() && !transcript.ends_with('\n') {
+            transcript.push('\n');
+        }
+        transcript.push_str(&report);
+        transcript.push('\n');
+        (transcript, screen::render(&interpreter.state().memory.0))
+    }
+
+    fn read_png(path: &Path) -> Vec<u8> {
+        let decoder = png::Decoder::new(File::open(path).expect("Failed to open screen"));
+        let mut reader = decoder.read_info().expect("Failed to decode screen");
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).expect("Failed to decode screen");
+        assert_eq!(
+            (info.width, info.height, info.color_type),
+            (WIDTH as u32, HEIGHT as u32, png::ColorType::Rgb),
+            "{:?} isn't a {}x{} RGB image",
+            path,
+            WIDTH,
+            HEIGHT
+        );
+        pixels.truncate(info.buffer_size());
+        pixels
+    }
+
+    fn write_png(path: &Path, pixels: &[u8]) {
+        let mut encoder = png::Encoder::new(File::create(path).expect("Failed to create screen"), WIDTH as u32, HEIGHT as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        let mut writer = encoder.write_header().expect("Failed to encode screen");
+        writer.write_image_data(pixels).expect("Failed to encode screen");
+    }
+
+    /// Checks a program against its expected files, returning what didn't match.
+    fn check(path: &Path, prefixed: bool) -> Vec<String> {
+        let (transcript, pixels) = run(path, prefixed);
+        let (out, png) = (path.with_extension("out"), path.with_extension("png"));
+        let mut failures = vec![];
+        if updating() {
+            fs::write(&out, &transcript).expect("Failed to write transcript");
+        } else {
+            match fs::read_to_string(&out) {
+                Ok(expected) if expected == transcript => {}
+                Ok(expected) => failures.push(format!(
+                    "{:?} doesn't match\n--- expected\n{}--- actual\n{}",
+                    out, expected, transcript
+                )),
+                Err(err) => failures.push(format!("{:?}: {}", out, err)),
+            }
+        }
+        // Screens are only checked for programs that have one
+        if png.exists() {
+            if updating() {
+                write_png(&png, &pixels);
+            } else if read_png(&png) != pixels {
+                failures.push(format!("{:?} doesn't match the screen", png));
+            }
+        }
+        failures
+    }
+
+    fn programs(dir: &str) -> Vec<PathBuf> {
+        let mut paths: Vec<_> = fs::read_dir(Path::new("programs").join(dir))
+            .expect("Directory not found")
+            .map(|entry| entry.expect("Failed to read entry").path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "zx"))
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn test_golden_programs() {
+        let mut failures = vec![];
+        for (dir, prefixed) in [("basic", false), ("intermediate", true)] {
+            let paths = programs(dir);
+            assert!(!paths.is_empty(), "No programs in {}", dir);
+            for path in paths {
+                failures.extend(check(&path, prefixed));
+            }
+        }
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+}
@@ -0,0 +1,260 @@
+use crate::console::Console;
+use crate::exec::{self, Dialect, Limits, Memory, Report, State, Value};
+use crate::parser::{find_line, Expr, Instr, Line};
+use crate::source::Sources;
+use crate::tape::Tape;
+use crate::trace::Tracer;
+
+/// Where a program is after the interpreter has run some of it.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Status {
+    /// There is more of the program to run.
+    Running,
+    /// The program ran off the end of its last line.
+    Finished,
+    /// The program stopped with a report, from STOP, BREAK or an error. CONTINUE resumes it.
+    Stopped(Report),
+}
+
+/// Runs a program, a statement at a time or until it stops, keeping its state between calls.
+#[derive(Debug, Default)]
+pub struct Interpreter<'a> {
+    lines: Vec<Line<'a>>,
+    state: State<'a>,
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(mut lines: Vec<Line<'a>>) -> Self {
+        let mut state = State::default();
+        state.vars.intern(&mut lines);
+        Interpreter { lines, state }
+    }
+
+    /// The program, which LOAD and MERGE can change while it runs.
+    pub fn lines(&self) -> &[Line<'a>] {
+        &self.lines
+    }
+
+    pub(crate) fn state(&self) -> &State<'a> {
+        &self.state
+    }
+
+    #[cfg(test)]
+    pub(crate) fn state_mut(&mut self) -> &mut State<'a> {
+        &mut self.state
+    }
+
+    /// Where the program prints to and reads from, which is the terminal unless it's set.
+    pub fn set_console(&mut self, console: Box<dyn Console>) {
+        self.state.console = console;
+    }
+
+    /// Limits on what the program can use, past which it stops with a report.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.state.limits = limits;
+    }
+
+    pub fn set_dialect(&mut self, dialect: Dialect) {
+        self.state.dialect = dialect;
+    }
+
+    /// Something to tell about each statement as it runs, which stops the program being compiled.
+    pub fn set_tracer(&mut self, tracer: Box<dyn Tracer>) {
+        self.state.tracer = Some(tracer);
+    }
+
+    /// The tape that LOAD, SAVE, MERGE and VERIFY use.
+    pub fn set_tape(&mut self, tape: Tape) {
+        self.state.tape = Some(tape);
+    }
+
+    /// Where LOAD and MERGE keep the listings they read, which they can't without it.
+    pub fn set_sources(&mut self, sources: &'a Sources) {
+        self.state.sources = Some(sources);
+    }
+
+    /// The Spectrum's 64K address space, which CODE is saved from and loaded into.
+    pub fn set_memory(&mut self, memory: Box<[u8]>) {
+        self.state.memory = Memory(memory);
+    }
+
+    /// Sets numeric variables, like those saved with a program.
+    pub fn load_vars<'n>(&mut self, vars: impl IntoIterator<Item = (&'n str, i64)>) -> anyhow::Result<()> {
+        self.state.load_vars(vars)
+    }
+
+    /// Where CONTINUE picks up, as a line number (or the next one after it) and 1-based statement.
+    pub fn set_continue(&mut self, line: usize, statement: usize) {
+        self.state.cont = Some((find_line(&self.lines, line), statement.saturating_sub(1)));
+    }
+
+    /// The indices of the line and statement that run next, if any.
+    fn next_statement(&self) -> Option<(usize, usize)> {
+        let (mut pc, mut stmt) = (self.state.pc, self.state.stmt);
+        while pc < self.lines.len() && stmt >= self.lines[pc].instr.statements().len() {
+            (pc, stmt) = (pc + 1, 0);
+        }
+        (pc < self.lines.len()).then_some((pc, stmt))
+    }
+
+    /// The line number and (1-based) statement that runs next, or `None` at the end.
+    pub fn position(&self) -> Option<(usize, usize)> {
+        self.next_statement()
+            .map(|(pc, stmt)| (self.lines[pc].number, stmt + 1))
+    }
+
+    /// The statement that runs next, or `None` at the end.
+    pub fn statement(&self) -> Option<&Instr<'a>> {
+        self.next_statement()
+            .map(|(pc, stmt)| &self.lines[pc].instr.statements()[stmt])
+    }
+
+    pub fn var(&self, name: &str) -> Option<i64> {
+        self.state.vars.get(self.state.vars.symbol(name)?)
+    }
+
+    /// Interns the names in an expression, so it can be evaluated with the program's variables.
+    pub(crate) fn intern(&mut self, expr: &mut Expr) {
+        self.state.vars.intern_expr(expr);
+    }
+
+    /// Evaluates an expression, once it's interned, with the program's variables.
+    pub(crate) fn eval<'e>(&'e self, expr: &'e Expr) -> anyhow::Result<Value<'e>> {
+        expr.eval(&self.state)
+    }
+
+    /// Moves to the start of a line (or the next one after it), like GO TO.
+    pub fn goto(&mut self, line: usize) {
+        self.state.jump(find_line(&self.lines, line), 0);
+    }
+
+    /// Runs the next statement.
+    pub fn step(&mut self) -> Status {
+        match exec::step(&mut self.lines, &mut self.state) {
+            Ok(true) => Status::Running,
+            Ok(false) => Status::Finished,
+            Err(report) => Status::Stopped(report),
+        }
+    }
+
+    /// Runs until the program finishes or stops. The program is compiled, to run faster, unless
+    /// there's a tracer to tell about each statement.
+    pub fn run(&mut self) -> Status {
+        match exec::run(&mut self.lines, &mut self.state) {
+            Ok(()) => Status::Finished,
+            Err(report) => Status::Stopped(report),
+        }
+    }
+
+    /// Runs until the program finishes or stops, or `pause` returns true, which is checked
+    /// after each statement. Pausing returns `Status::Running`.
+    pub fn run_until(&mut self, mut pause: impl FnMut(&Self) -> bool) -> Status {
+        exec::take_break();
+        loop {
+            match self.step() {
+                Status::Running if !pause(self) => {}
+                status => return status,
+            }
+        }
+    }
+
+    /// Runs a statement typed without a line number, which may run the program (RUN, GO TO,
+    /// CONTINUE, ...).
+    pub fn execute(&mut self, instr: &Instr<'a>) -> Result<(), Report> {
+        let mut instr = instr.clone();
+        self.state.vars.intern_instr(&mut instr);
+        exec::execute_immediate(&instr, &mut self.lines, &mut self.state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Interpreter, Status};
+    use crate::console::Capture;
+    use crate::exec::{ErrorCode, Limits};
+    use crate::parser::{parse_file, Instr};
+
+    const PROGRAM: &str = "10 LET a=1: LET b=2\n20 STOP\n30 LET a=a+b\n";
+
+    #[test]
+    fn test_step() {
+        let mut interpreter = Interpreter::new(parse_file(PROGRAM, true).unwrap());
+        assert_eq!(interpreter.position(), Some((10, 1)));
+        assert_eq!(interpreter.step(), Status::Running);
+        assert_eq!(interpreter.position(), Some((10, 2)));
+        assert_eq!(interpreter.var("a"), Some(1));
+        assert_eq!(interpreter.step(), Status::Running);
+        assert_eq!(interpreter.position(), Some((20, 1)));
+        match interpreter.step() {
+            Status::Stopped(report) => assert_eq!((report.code, report.line), (ErrorCode::Stop, 20)),
+            status => panic!("Expected STOP, found {:?}", status),
+        }
+        interpreter.execute(&Instr::Continue).unwrap();
+        assert_eq!(interpreter.var("a"), Some(3));
+        assert_eq!(interpreter.position(), None);
+        assert_eq!(interpreter.step(), Status::Finished);
+    }
+
+    #[test]
+    fn test_run_until() {
+        let mut interpreter = Interpreter::new(parse_file(PROGRAM, true).unwrap());
+        let status = interpreter.run_until(|i| i.var("b").is_some());
+        assert_eq!(status, Status::Running);
+        assert_eq!(interpreter.position(), Some((20, 1)));
+        interpreter.goto(30);
+        assert_eq!(interpreter.run(), Status::Finished);
+        assert_eq!(interpreter.var("A"), Some(3));
+    }
+
+    fn stopped(status: Status) -> String {
+        match status {
+            Status::Stopped(report) => report.to_string(),
+            status => panic!("Expected the program to stop, found {:?}", status),
+        }
+    }
+
+    #[test]
+    fn test_limits() {
+        let forever = parse_file("10 LET a=1\n20 LET b=a\n30 GO TO 20", true).unwrap();
+        let mut interpreter = Interpreter::new(forever.clone());
+        interpreter.state_mut().limits.statements = Some(10);
+        let report = stopped(interpreter.run());
+        assert_eq!(report, "X Limit exceeded, 30:1 (More than 10 statements)");
+        // CONTINUE tries the statement again, and is stopped again
+        assert!(interpreter.execute(&Instr::Continue).is_err());
+
+        let mut interpreter = Interpreter::new(forever);
+        interpreter.state_mut().limits.timeout = Some(std::time::Duration::from_millis(10));
+        assert!(stopped(interpreter.run()).starts_with("X Limit exceeded, "));
+
+        let program = parse_file("10 LET a=1\n20 LET a=2: LET bc=3\n30 PRINT a;bc", true).unwrap();
+        let mut interpreter = Interpreter::new(program.clone());
+        interpreter.state_mut().limits.variables = Some(1);
+        let report = stopped(interpreter.run());
+        assert_eq!(report, "X Limit exceeded, 20:2 (More than 1 variables, defining bc)");
+        let mut interpreter = Interpreter::new(program.clone());
+        interpreter.state_mut().limits.memory = Some(12);
+        let report = stopped(interpreter.run());
+        assert_eq!(report, "X Limit exceeded, 20:2 (Variables take more than 12 bytes, defining bc)");
+
+        // A string counts at its new length every time it's set, not just the first
+        let growing = parse_file("10 LET a$=\"ab\"\n20 LET a$=\"abcdef\"", true).unwrap();
+        let mut interpreter = Interpreter::new(growing);
+        interpreter.state_mut().limits.memory = Some(8);
+        let report = stopped(interpreter.run());
+        assert_eq!(report, "X Limit exceeded, 20:1 (Variables take more than 8 bytes, defining a$)");
+        assert_eq!(interpreter.state().vars.strings().next().map(|(_, value)| value.len()), Some(2));
+
+        let capture = Capture::default();
+        let mut interpreter = Interpreter::new(program);
+        interpreter.set_console(Box::new(capture.clone()));
+        interpreter.state_mut().limits = Limits {
+            statements: Some(4),
+            memory: Some(13),
+            output: Some(3),
+            ..Limits::default()
+        };
+        assert_eq!(interpreter.run(), Status::Finished);
+        assert_eq!(capture.output(), "23\n");
+    }
+}
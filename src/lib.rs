@@ -0,0 +1,41 @@
+//! An interpreter for ZX Spectrum BASIC.
+//!
+//! Parse a program with [`parse_file`], then run it with an [`Interpreter`]:
+//!
+//! ```
+//! use zx_spectrum::{parse_file, Interpreter, Status};
+//!
+//! let lines = parse_file("10 LET a=6*7", true).unwrap();
+//! let mut interpreter = Interpreter::new(lines);
+//! assert_eq!(interpreter.run(), Status::Finished);
+//! assert_eq!(interpreter.var("a"), Some(42));
+//! ```
+mod check;
+pub mod console;
+mod debugger;
+mod exec;
+mod golden_tests;
+mod interpreter;
+mod parser;
+mod profile;
+mod renumber;
+mod repl;
+pub mod screen;
+mod snapshot;
+mod source;
+mod tape;
+mod tokenizer;
+mod trace;
+
+pub use check::{check, Warning};
+pub use debugger::{Breakpoint, Debugger, Pause};
+pub use exec::{request_break, Dialect, ErrorCode, Limits, Report};
+pub use interpreter::{Interpreter, Status};
+pub use parser::{parse_file, parse_file_recovering, Expr, Instr, Line, ParseDiagnostic};
+pub use profile::{Profile, Stat};
+pub use renumber::renumber;
+pub use repl::Repl;
+pub use snapshot::{Snapshot, SnapshotProgram};
+pub use source::Sources;
+pub use tape::{load_program, save_program, Tape, TapeProgram};
+pub use trace::{TraceFormat, TraceWriter, Tracer};
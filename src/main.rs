@@ -1,18 +1,222 @@
-use anyhow::{anyhow, Context, Error};
+use std::fs::File;
+use std::io::{stderr, stdin, stdout, BufWriter, Write};
+
+use anyhow::{anyhow, bail, Context, Error};
 use clap::Parser;
-use std::fs::read_to_string;
+use cli::{Command, FmtArgs, RenumberArgs, ReplArgs, RunArgs, SourceArgs, TapArgs};
+use zx_spectrum::{
+    parse_file, parse_file_recovering, request_break, save_program, Debugger, Dialect, ErrorCode,
+    Interpreter, Profile, Repl, Sources, Status, Tape, TraceWriter,
+};
 mod cli;
-mod exec;
-mod parser;
+
+/// The exit status when a program goes over a limit given on the command line.
+const LIMIT_EXCEEDED_STATUS: i32 = 3;
 
 fn main() -> Result<(), Error> {
     let args = cli::Args::parse();
-    let content = read_to_string(args.path).context("Failed to read file.")?;
-    // This is very finnicky, .context("Failed to parse file.") fails to compile
-    let instrs = parser::parse_file(&content, args.prefixed)
-        .map_err(|e| anyhow!("Failed to parse file: {:#?}", e))?;
+    ctrlc::set_handler(request_break).context("Failed to set Ctrl-C handler.")?;
+    match args.command {
+        None => run(args.run),
+        Some(Command::Run(run_args)) => run(run_args),
+        Some(Command::Check(source)) => check(source),
+        Some(Command::Renumber(renumber_args)) => renumber(renumber_args),
+        Some(Command::Fmt(fmt_args)) => fmt(fmt_args),
+        Some(Command::Repl(repl_args)) => repl(repl_args),
+        Some(Command::Tap(tap_args)) => tap(tap_args),
+    }
+}
 
-    exec::execute(instrs).context("Failed to execute program:")?;
+fn run(args: RunArgs) -> Result<(), Error> {
+    let sources = Sources::default();
+    let source = args.source.read()?;
+    let (lines, diagnostics) = parse_file_recovering(&source.text, source.prefixed);
+    for diagnostic in &diagnostics {
+        eprintln!("{}\n", diagnostic);
+    }
+    if !diagnostics.is_empty() && !args.recover {
+        bail!("Failed to parse file: {} error(s)", diagnostics.len());
+    }
+
+    // A program from tape or a snapshot runs like GO TO its auto-start line (or its first), with
+    // its saved variables
+    let mut interpreter = Interpreter::new(lines);
+    if let Some(line) = source.autostart {
+        interpreter.goto(line);
+    }
+    if let Some(tape) = args.tape {
+        interpreter.set_tape(Tape::new(tape));
+    }
+    interpreter.set_sources(&sources);
+    interpreter.set_limits(args.limits.limits());
+    if args.extended {
+        interpreter.set_dialect(Dialect::Extended);
+    }
+    if args.trace {
+        let out: Box<dyn Write> = match &args.trace_file {
+            Some(path) => Box::new(BufWriter::new(File::create(path).context("Failed to create trace file.")?)),
+            None => Box::new(stderr()),
+        };
+        interpreter.set_tracer(Box::new(TraceWriter::new(out, args.trace_format)));
+    }
+    interpreter
+        .load_vars(source.variables.iter().map(|(name, value)| (name.as_str(), *value)))
+        .context("Failed to load the program's variables.")?;
+    if let Some(memory) = source.memory {
+        interpreter.set_memory(memory);
+    }
+    if let Some((line, statement)) = source.cont {
+        interpreter.set_continue(line, statement);
+    }
+    if args.debug {
+        return debug(Debugger::new(interpreter, &sources));
+    }
+    let status = match args.profile {
+        true => {
+            let mut profile = Profile::default();
+            let status = profile.run(&mut interpreter);
+            eprint!("\n{}", profile.report());
+            if let Some(path) = &args.profile_folded {
+                std::fs::write(path, profile.folded()).context("Failed to write folded stacks.")?;
+            }
+            status
+        }
+        false => interpreter.run(),
+    };
+    match status {
+        Status::Stopped(report) if report.code == ErrorCode::Stop => eprintln!("{}", report),
+        Status::Stopped(report) if report.code == ErrorCode::LimitExceeded => {
+            // A distinct exit status, so scripts can tell a program that was cut off from one that failed
+            eprintln!("{}", report);
+            drop(interpreter); // Exiting doesn't drop it, which flushes the trace
+            std::process::exit(LIMIT_EXCEEDED_STATUS);
+        }
+        Status::Stopped(report) => Err(report).context("Failed to execute program:")?,
+        Status::Finished | Status::Running => {}
+    }
 
     Ok(())
 }
+
+/// Reads debugger commands from stdin until `quit`. The debugger writes to stderr, to keep
+/// it apart from the program's output.
+fn debug(mut debugger: Debugger) -> Result<(), Error> {
+    eprintln!("{}\nType help for a list of commands", debugger.position());
+    let mut input = String::new();
+    loop {
+        eprint!("(debug) ");
+        input.clear();
+        if stdin().read_line(&mut input)? == 0 || matches!(input.trim(), "quit" | "q") {
+            return Ok(());
+        }
+        match debugger.command(&input) {
+            Ok(text) if text.is_empty() => {}
+            Ok(text) => eprintln!("{}", text),
+            Err(e) => eprintln!("{:#}", e),
+        }
+    }
+}
+
+fn check(args: SourceArgs) -> Result<(), Error> {
+    let source = args.read()?;
+    let (lines, diagnostics) = parse_file_recovering(&source.text, source.prefixed);
+    for diagnostic in &diagnostics {
+        println!("{}\n", diagnostic);
+    }
+    let warnings = zx_spectrum::check(&lines);
+    for warning in &warnings {
+        println!("warning: {}", warning);
+    }
+    if !diagnostics.is_empty() || !warnings.is_empty() {
+        bail!(
+            "{} error(s), {} warning(s)",
+            diagnostics.len(),
+            warnings.len()
+        );
+    }
+    Ok(())
+}
+
+fn renumber(args: RenumberArgs) -> Result<(), Error> {
+    let source = args.source.read()?;
+    let lines = parse_file(&source.text, source.prefixed)
+        .map_err(|e| anyhow!("Failed to parse file: {:#?}", e))?;
+    for line in zx_spectrum::renumber(&lines, args.start, args.step)? {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+fn fmt(args: FmtArgs) -> Result<(), Error> {
+    let source = args.source.read()?;
+    let lines = parse_file(&source.text, source.prefixed)
+        .map_err(|e| anyhow!("Failed to parse file: {:#?}", e))?;
+    let listing: String = lines
+        .iter()
+        .map(|line| match source.prefixed {
+            true => format!("{}\n", line),
+            false => format!("{}\n", line.instr),
+        })
+        .collect();
+    if args.write {
+        if args.source.is_tape() {
+            bail!("Can't write a listing over a tape image");
+        }
+        let path = args.source.path.as_ref().expect("clap requires a path");
+        std::fs::write(path, listing).context("Failed to write file.")?;
+    } else {
+        print!("{}", listing);
+    }
+    Ok(())
+}
+
+fn repl(args: ReplArgs) -> Result<(), Error> {
+    let sources = Sources::default();
+    let mut repl = Repl::new(&sources);
+    if let Some(tape) = args.tape {
+        repl.set_tape(Tape::new(tape));
+    }
+    if args.extended {
+        repl.set_dialect(Dialect::Extended);
+    }
+    if let Some(path) = args.path {
+        let content = std::fs::read_to_string(path).context("Failed to read file.")?;
+        repl.load(&content, args.prefixed)?;
+    }
+    run_repl(repl)
+}
+
+fn run_repl(mut repl: Repl) -> Result<(), Error> {
+    let mut input = String::new();
+    loop {
+        print!("> ");
+        stdout().flush()?;
+        input.clear();
+        if stdin().read_line(&mut input)? == 0 {
+            return Ok(());
+        }
+        match repl.enter(&input) {
+            Ok(true) => println!("0 OK"),
+            Ok(false) => {}
+            Err(e) => println!("{:#}", e),
+        }
+    }
+}
+
+fn tap(args: TapArgs) -> Result<(), Error> {
+    let source = args.source.read()?;
+    let lines = parse_file(&source.text, source.prefixed)
+        .map_err(|e| anyhow!("Failed to parse file: {:#?}", e))?;
+    let path = args.source.path.as_ref().expect("clap requires a path");
+    let name = match args.name {
+        Some(name) => name,
+        None => path.file_stem().unwrap_or_default().to_string_lossy().chars().take(10).collect(),
+    };
+    if name.len() > 10 || !name.is_ascii() {
+        bail!("Tape names are at most 10 ASCII characters: {:?}", name);
+    }
+    let autostart = args.autostart.or(source.autostart);
+    let variables = source.variables.iter().map(|(name, value)| (name.as_str(), *value));
+    let tape = save_program(&name, &lines, autostart, variables)?;
+    std::fs::write(&args.output, tape).context("Failed to write file.")
+}
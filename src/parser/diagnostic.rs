@@ -0,0 +1,34 @@
+use std::fmt::{Display, Formatter, Result};
+
+use nom::error::convert_error;
+
+use super::parse_tools::NomErr;
+
+/// A parse error tied to the (1-based) source line it came from.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParseDiagnostic {
+    pub line: usize,
+    pub source: String,
+    pub message: String,
+}
+
+impl ParseDiagnostic {
+    pub(crate) fn new(line: usize, source: &str, err: nom::Err<NomErr>) -> Self {
+        let message = match err {
+            nom::Err::Error(e) | nom::Err::Failure(e) => convert_error(source, e),
+            nom::Err::Incomplete(_) => "Unexpected end of line".to_string(),
+        };
+        ParseDiagnostic {
+            line,
+            source: source.to_string(),
+            message,
+        }
+    }
+}
+
+impl Display for ParseDiagnostic {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        writeln!(f, "line {}: {}", self.line, self.source)?;
+        write!(f, "{}", self.message.trim_end())
+    }
+}
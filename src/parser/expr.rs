@@ -1,14 +1,15 @@
 use nom::branch::alt;
 use nom::bytes::complete::tag;
 use nom::bytes::complete::take_till;
-use nom::character::complete::{alpha1, char, i64};
-use nom::combinator::{cut, map};
+use nom::character::complete::{alphanumeric0, char, i64, satisfy};
+use nom::combinator::{cut, map, recognize};
 use nom::error::context;
 use nom::multi::many0;
 use nom::sequence::{pair, preceded, terminated};
 
-use crate::parser::lower::LowerCase;
 use crate::parser::parse_tools::{with_whitespaces, ParseResult};
+use crate::parser::symbol::{Ident, Symbols};
+use crate::tokenizer::find_keyword;
 
 use super::parse_tools::ident;
 
@@ -16,7 +17,7 @@ type BExpr<'a> = Box<Expr<'a>>;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Expr<'a> {
-    Ident(LowerCase<'a>),
+    Ident(Ident<'a>),
     Int(i64),
     String(&'a str),
     Add(BExpr<'a>, BExpr<'a>),
@@ -29,6 +30,8 @@ pub enum Expr<'a> {
     Ge(BExpr<'a>, BExpr<'a>),
     Le(BExpr<'a>, BExpr<'a>),
     Ne(BExpr<'a>, BExpr<'a>),
+    Err, // The report code of the error ON ERROR GO TO trapped; extended dialect only
+    Erl, // The line of the error ON ERROR GO TO trapped; extended dialect only
 }
 
 macro_rules! parse_general {
@@ -49,8 +52,36 @@ macro_rules! parse_general {
 }
 
 impl Expr<'_> {
+    /// A number variable's name: a letter, then letters and digits, which may be split into
+    /// words by spaces, like `my total 2`. A word that's a keyword, like `TO`, ends it. A string
+    /// variable's name is a single letter and `$`, like `a$`.
+    pub(crate) fn parse_name(s: &str) -> ParseResult<&str> {
+        let (mut rest, word) = recognize(pair(satisfy(|c| c.is_ascii_alphabetic()), alphanumeric0))(s)?;
+        if word.len() == 1 && rest.starts_with('$') {
+            return Ok((&rest[1..], &s[..2]));
+        }
+        loop {
+            let word = rest.trim_start_matches(' ');
+            let len = word.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(word.len());
+            if word.len() == rest.len() || len == 0 || find_keyword(word).is_some() {
+                break;
+            }
+            rest = &word[len..];
+        }
+        Ok((rest, &s[..s.len() - rest.len()]))
+    }
+
     pub(crate) fn parse_ident(s: &str) -> ParseResult<Expr> {
-        map(alpha1, ident)(s)
+        map(Expr::parse_name, ident)(s)
+    }
+
+    /// A variable, or ERR or ERL, which are written like one.
+    fn parse_value(s: &str) -> ParseResult<Expr> {
+        map(Expr::parse_name, |name| match name.to_ascii_lowercase().as_str() {
+            "err" => Expr::Err,
+            "erl" => Expr::Erl,
+            _ => ident(name),
+        })(s)
     }
 
     fn parse_atom(s: &str) -> ParseResult<Expr> {
@@ -65,7 +96,7 @@ impl Expr<'_> {
                     )),
                 ),
             ), // Parentheses
-            Expr::parse_ident,
+            Expr::parse_value,
             map(i64, Expr::Int),
             preceded(
                 char('"'),
@@ -86,6 +117,27 @@ impl Expr<'_> {
         parse_general!(Expr::parse_factor, s, "<=", ">=", "<>", "<", ">", "=") // Parse 2-char operators first
     }
 
+    /// Interns the names of the variables in the expression, giving them their symbols.
+    pub fn intern(&mut self, symbols: &mut Symbols) {
+        match self {
+            Expr::Ident(ident) => ident.intern(symbols),
+            Expr::Int(_) | Expr::String(_) | Expr::Err | Expr::Erl => {}
+            Expr::Add(a, b)
+            | Expr::Sub(a, b)
+            | Expr::Mul(a, b)
+            | Expr::Div(a, b)
+            | Expr::Gt(a, b)
+            | Expr::Lt(a, b)
+            | Expr::Eq(a, b)
+            | Expr::Ge(a, b)
+            | Expr::Le(a, b)
+            | Expr::Ne(a, b) => {
+                a.intern(symbols);
+                b.intern(symbols);
+            }
+        }
+    }
+
     fn parse_fn<'a>(op: &str, acc: Expr<'a>, expr: Expr<'a>) -> Expr<'a> {
         let fun = match op {
             "+" => Expr::Add,
@@ -1,35 +1,193 @@
 use nom::branch::alt;
 use nom::bytes::complete::tag_no_case;
-use nom::character::complete::{alpha1, char, digit1, multispace0, multispace1, one_of};
-use nom::combinator::{all_consuming, cut, map, opt, rest, verify};
+use nom::character::complete::{alpha1, char, digit1, multispace0, multispace1, none_of, one_of};
+use nom::combinator::{all_consuming, cut, map, map_res, opt, peek, recognize, rest, success, verify};
 use nom::error::context;
-use nom::multi::{many0, separated_list1};
+use nom::multi::{many0, many1, separated_list1};
 use nom::sequence::{pair, preceded, separated_pair, terminated, tuple};
 
 use crate::parser::expr::Expr;
 use crate::parser::parse_tools::{with_whitespaces, ParseResult};
+use crate::parser::symbol::{Ident, Symbols};
 
 use super::parse_tools::ident;
 
-// TODO: Add support for string vars, denoted with training $
 #[derive(Debug, PartialEq, Clone)]
 pub enum Instr<'a> {
     Print(Option<Expr<'a>>, Vec<(char,Expr<'a>)>, Option<char>),              // Print(first_expr, rest, last)
     Assign(Expr<'a>, Expr<'a>),        // Assign(Ident, Expr)
-    Input(Option<Expr<'a>>, Expr<'a>), // Input(Expr, Ident)
+    Input(Vec<InputItem<'a>>),
     Rem(&'a str),
     Goto(usize),
+    Gosub(usize),
+    Return,
+    Stop,
+    List(Option<usize>), // List(first line)
+    Run(Option<usize>),  // Run(first line)
     Clear,
-    IfThen(Expr<'a>, Box<Instr<'a>>),
+    Continue,
+    Cls,
+    IfThen(Expr<'a>, Box<Instr<'a>>), // IfThen(condition, first statement after THEN); if false, the rest of the line is skipped
     Multi(Vec<Instr<'a>>),
     For(Expr<'a>, Expr<'a>, Expr<'a>, Expr<'a>), // For(ident, start, end, step)
     Next(Expr<'a>),
+    Tape(TapeCommand, Expr<'a>, TapeData<'a>), // Tape(command, file name, what to save or load)
+    OnErrorGoto(usize),   // OnErrorGoto(line), or 0 to stop trapping errors; extended dialect only
+    Resume(bool),         // Resume(NEXT), to the statement after the error rather than retrying it
+    Renumber(Option<usize>, Option<usize>), // Renumber(start, step)
+    SyntaxError(&'a str), // Unparseable line, kept so the program can still run up to it
 }
 
-impl Instr<'_> {
-    pub fn parse_prefixed(s: &str) -> ParseResult<Instr> {
-        preceded(
-            context("Prefixed line", pair(digit1, multispace1)),
+/// The parts of an INPUT statement: what to print, like PRINT, and the variables to read.
+#[derive(Debug, PartialEq, Clone)]
+pub enum InputItem<'a> {
+    Prompt(Expr<'a>),       // A string, or an expression in brackets, to print
+    Var(Ident<'a>),         // A number, or a string typed between quote marks
+    Line(Ident<'a>),        // LINE a$, a string taken as typed
+    At(Expr<'a>, Expr<'a>), // At(line, column) in the lower screen
+    Separator(char),        // ';', ',' or '\'', like PRINT's
+}
+
+/// SAVE, LOAD, VERIFY and MERGE, which all take a file name and what to save or load.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TapeCommand {
+    Save,
+    Load,
+    Verify,
+    Merge,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum TapeData<'a> {
+    Program(Option<usize>),                   // Program(LINE to start at, only for SAVE)
+    Code(Option<Expr<'a>>, Option<Expr<'a>>), // Code(start, length)
+    Data(&'a str),                            // Data(array name, e.g. a or a$)
+    Screen,                                   // SCREEN$, the same as CODE 16384,6912
+}
+
+impl TapeData<'_> {
+    /// Whether `command` can be followed by this, e.g. only SAVE takes LINE.
+    fn allowed(&self, command: TapeCommand) -> bool {
+        match (command, self) {
+            (TapeCommand::Merge, TapeData::Program(None)) => true,
+            (TapeCommand::Merge, _) => false,
+            (TapeCommand::Save, TapeData::Code(start, length)) => start.is_some() && length.is_some(),
+            (TapeCommand::Save, _) => true,
+            (_, TapeData::Program(line)) => line.is_none(),
+            _ => true,
+        }
+    }
+}
+
+impl<'a> Instr<'a> {
+    /// The keyword the statement starts with, e.g. `GO SUB`. Lines with several statements
+    /// and ones that didn't parse don't have one.
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            Instr::Print(..) => "PRINT",
+            Instr::Assign(..) => "LET",
+            Instr::Input(..) => "INPUT",
+            Instr::Rem(_) => "REM",
+            Instr::Goto(_) => "GO TO",
+            Instr::Gosub(_) => "GO SUB",
+            Instr::Return => "RETURN",
+            Instr::Stop => "STOP",
+            Instr::List(_) => "LIST",
+            Instr::Run(_) => "RUN",
+            Instr::Clear => "CLEAR",
+            Instr::Continue => "CONTINUE",
+            Instr::Cls => "CLS",
+            Instr::IfThen(..) => "IF",
+            Instr::For(..) => "FOR",
+            Instr::Next(_) => "NEXT",
+            Instr::Tape(TapeCommand::Save, ..) => "SAVE",
+            Instr::Tape(TapeCommand::Load, ..) => "LOAD",
+            Instr::Tape(TapeCommand::Verify, ..) => "VERIFY",
+            Instr::Tape(TapeCommand::Merge, ..) => "MERGE",
+            Instr::OnErrorGoto(_) => "ON ERROR",
+            Instr::Resume(_) => "RESUME",
+            Instr::Renumber(..) => "RENUMBER",
+            Instr::Multi(_) | Instr::SyntaxError(_) => "",
+        }
+    }
+
+    /// The statements of a line, which are separated by `:`.
+    pub fn statements(&self) -> &[Instr<'a>] {
+        match self {
+            Instr::Multi(instrs) => instrs,
+            instr => std::slice::from_ref(instr),
+        }
+    }
+
+    /// Mutable references to every line number the instruction refers to, for renumbering.
+    pub fn line_refs_mut(&mut self) -> Vec<&mut usize> {
+        match self {
+            Instr::Goto(number)
+            | Instr::Gosub(number)
+            | Instr::List(Some(number))
+            | Instr::Run(Some(number))
+            | Instr::Tape(_, _, TapeData::Program(Some(number))) => vec![number],
+            Instr::OnErrorGoto(number) if *number != 0 => vec![number],
+            Instr::IfThen(_, instr) => instr.line_refs_mut(),
+            Instr::Multi(instrs) => instrs.iter_mut().flat_map(Instr::line_refs_mut).collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Interns the names of the variables the instruction uses, giving them their symbols.
+    pub fn intern(&mut self, symbols: &mut Symbols) {
+        match self {
+            Instr::Print(first, rest, _) => {
+                for expr in first.iter_mut().chain(rest.iter_mut().map(|(_, expr)| expr)) {
+                    expr.intern(symbols);
+                }
+            }
+            Instr::Assign(ident, expr) => {
+                ident.intern(symbols);
+                expr.intern(symbols);
+            }
+            Instr::Input(items) => {
+                for item in items {
+                    match item {
+                        InputItem::Prompt(expr) => expr.intern(symbols),
+                        InputItem::Var(ident) | InputItem::Line(ident) => ident.intern(symbols),
+                        InputItem::At(line, column) => {
+                            line.intern(symbols);
+                            column.intern(symbols);
+                        }
+                        InputItem::Separator(_) => {}
+                    }
+                }
+            }
+            Instr::IfThen(condition, instr) => {
+                condition.intern(symbols);
+                instr.intern(symbols);
+            }
+            Instr::Multi(instrs) => instrs.iter_mut().for_each(|instr| instr.intern(symbols)),
+            Instr::For(ident, start, end, step) => {
+                for expr in [ident, start, end, step] {
+                    expr.intern(symbols);
+                }
+            }
+            Instr::Next(ident) => ident.intern(symbols),
+            Instr::Tape(_, name, data) => {
+                name.intern(symbols);
+                if let TapeData::Code(start, length) = data {
+                    for expr in start.iter_mut().chain(length) {
+                        expr.intern(symbols);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn parse_prefixed(s: &str) -> ParseResult<(usize, Instr)> {
+        pair(
+            context(
+                "Prefixed line",
+                terminated(map_res(digit1, str::parse), multispace1),
+            ),
             Instr::parse,
         )(s)
     }
@@ -41,7 +199,10 @@ impl Instr<'_> {
                 // Cannot cut the multispace1 because we want to fall to the empty print statement case. Could refactor to avoid this?
                 terminated(
                     tag_no_case("print"),
-                    context("Space needed after print statement", multispace1),
+                    context(
+                        "Space needed after print statement",
+                        terminated(multispace1, peek(none_of(":"))),
+                    ),
                 ),
                 // TODO: Trailing '
                 cut(map(
@@ -59,26 +220,56 @@ impl Instr<'_> {
                 terminated(tag_no_case("if"), multispace1),
                 cut(pair(
                     Expr::parse,
-                    preceded(with_whitespaces(tag_no_case("then")), Instr::parse),
+                    preceded(with_whitespaces(tag_no_case("then")), Instr::parse_inner),
                 )),
             ),
-            |(expr, instrs)| Instr::IfThen(expr, Box::new(instrs)),
+            |(expr, instr)| Instr::IfThen(expr, Box::new(instr)),
         )(s)
     }
 
+    /// A variable to read into, or anything else to print. Like on the Spectrum, an item that
+    /// starts with a letter is a variable, and nothing more, so printing one, or a sum with
+    /// one, takes brackets.
+    fn parse_input_expr(s: &str) -> ParseResult<InputItem> {
+        match s.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            true => map(map(Expr::parse_name, Ident::new), InputItem::Var)(s),
+            false => map(Expr::parse, InputItem::Prompt)(s),
+        }
+    }
+
+    fn parse_input_item(s: &str) -> ParseResult<InputItem> {
+        alt((
+            map(one_of(";,'"), InputItem::Separator),
+            map(
+                preceded(
+                    pair(tag_no_case("at"), multispace1),
+                    separated_pair(Expr::parse, with_whitespaces(char(',')), Expr::parse),
+                ),
+                |(line, column)| InputItem::At(line, column),
+            ),
+            preceded(
+                pair(tag_no_case("line"), multispace1),
+                cut(context(
+                    "string variable after LINE",
+                    map(verify(map(Expr::parse_name, Ident::new), Ident::is_string), InputItem::Line),
+                )),
+            ),
+            Instr::parse_input_expr,
+        ))(s)
+    }
+
     fn parse_input(s: &str) -> ParseResult<Instr> {
         map(
             preceded(
                 terminated(tag_no_case("input"), multispace1),
-                cut(pair(
-                    Expr::parse,
-                    opt(preceded(with_whitespaces(char(',')), Expr::parse)),
-                )),
+                // Items run together without a separator could be read more than one way
+                cut(verify(many1(with_whitespaces(Instr::parse_input_item)), |items: &Vec<_>| {
+                    items.windows(2).all(|pair| {
+                        matches!(pair[0], InputItem::Separator(_)) || matches!(pair[1], InputItem::Separator(_))
+                    })
+                })),
             ),
-            |(expr1, expr2)| match expr2 {
-                None => Instr::Input(None, expr1),
-                Some(val) => Instr::Input(Some(expr1), val),
-            },
+            Instr::Input,
         )(s)
     }
 
@@ -132,6 +323,86 @@ impl Instr<'_> {
         )(s)
     }
 
+    fn parse_tape_data(s: &str) -> ParseResult<TapeData> {
+        let code_args = pair(Expr::parse, opt(preceded(with_whitespaces(char(',')), Expr::parse)));
+        alt((
+            map(
+                preceded(with_whitespaces(tag_no_case("line")), map_res(digit1, str::parse)),
+                |number| TapeData::Program(Some(number)),
+            ),
+            map(
+                preceded(with_whitespaces(tag_no_case("code")), opt(code_args)),
+                |args| match args {
+                    Some((start, length)) => TapeData::Code(Some(start), length),
+                    None => TapeData::Code(None, None),
+                },
+            ),
+            map(
+                preceded(
+                    with_whitespaces(tag_no_case("data")),
+                    terminated(
+                        recognize(pair(verify(alpha1, |x: &str| x.len() == 1), opt(char('$')))),
+                        with_whitespaces(tag_no_case("()")),
+                    ),
+                ),
+                TapeData::Data,
+            ),
+            map(with_whitespaces(tag_no_case("screen$")), |_| TapeData::Screen),
+            success(TapeData::Program(None)),
+        ))(s)
+    }
+
+    fn parse_tape(s: &str) -> ParseResult<Instr> {
+        let command = alt((
+            map(tag_no_case("save"), |_| TapeCommand::Save),
+            map(tag_no_case("load"), |_| TapeCommand::Load),
+            map(tag_no_case("verify"), |_| TapeCommand::Verify),
+            map(tag_no_case("merge"), |_| TapeCommand::Merge),
+        ));
+        let (s, command) = terminated(command, multispace0)(s)?;
+        cut(map(
+            verify(pair(Expr::parse, Instr::parse_tape_data), move |(_, data)| {
+                data.allowed(command)
+            }),
+            move |(name, data)| Instr::Tape(command, name, data),
+        ))(s)
+    }
+
+    fn parse_on_error(s: &str) -> ParseResult<Instr> {
+        map(
+            preceded(
+                tuple((tag_no_case("on"), multispace1, tag_no_case("error"), multispace1)),
+                cut(preceded(tag_no_case("go to "), map_res(digit1, str::parse))),
+            ),
+            Instr::OnErrorGoto,
+        )(s)
+    }
+
+    fn parse_resume(s: &str) -> ParseResult<Instr> {
+        map(
+            preceded(
+                tag_no_case("resume"),
+                opt(preceded(multispace1, tag_no_case("next"))),
+            ),
+            |next| Instr::Resume(next.is_some()),
+        )(s)
+    }
+
+    /// `RENUMBER [start][,step]`, which both default to 10.
+    fn parse_renumber(s: &str) -> ParseResult<Instr> {
+        let number = || map_res(digit1, str::parse);
+        map(
+            preceded(
+                tag_no_case("renumber"),
+                pair(
+                    opt(preceded(multispace0, number())),
+                    opt(preceded(with_whitespaces(char(',')), number())),
+                ),
+            ),
+            |(start, step)| Instr::Renumber(start, step),
+        )(s)
+    }
+
     pub fn parse_inner(s: &str) -> ParseResult<Instr> {
         alt((
             context("print statement", Instr::parse_print),
@@ -146,19 +417,58 @@ impl Instr<'_> {
             context("input statement", Instr::parse_input),
             context(
                 "goto statement",
-                map(preceded(tag_no_case("go to "), digit1), |x: &str| {
-                    Instr::Goto(x.parse().expect("Line no. too large, this is insanity"))
-                    // TODO: Handle error
-                }),
+                map(
+                    preceded(tag_no_case("go to "), map_res(digit1, str::parse)),
+                    Instr::Goto,
+                ),
             ),
-            context("cls statement", map(tag_no_case("cls"), |_| Instr::Clear)),
+            context(
+                "go sub statement",
+                map(
+                    preceded(tag_no_case("go sub "), map_res(digit1, str::parse)),
+                    Instr::Gosub,
+                ),
+            ),
+            context("return statement", map(tag_no_case("return"), |_| Instr::Return)),
+            context("cls statement", map(tag_no_case("cls"), |_| Instr::Cls)),
             context("if then statement", Instr::parse_if_then),
             context(
                 "stop statement",
-                map(tag_no_case("stop"), |_| Instr::Goto(99999999999)),
+                map(tag_no_case("stop"), |_| Instr::Stop),
+            ),
+            context(
+                "list statement",
+                map(
+                    preceded(
+                        tag_no_case("list"),
+                        opt(preceded(multispace1, map_res(digit1, str::parse))),
+                    ),
+                    Instr::List,
+                ),
+            ),
+            context(
+                "run statement",
+                map(
+                    preceded(
+                        tag_no_case("run"),
+                        opt(preceded(multispace1, map_res(digit1, str::parse))),
+                    ),
+                    Instr::Run,
+                ),
+            ),
+            context("clear statement", map(tag_no_case("clear"), |_| Instr::Clear)),
+            context(
+                "continue statement",
+                map(alt((tag_no_case("continue"), tag_no_case("cont"))), |_| {
+                    Instr::Continue
+                }),
             ),
             context("for loop", Instr::parse_for),
             context("next statement", Instr::parse_next),
+            context("tape statement", Instr::parse_tape),
+            context("on error statement", Instr::parse_on_error),
+            context("resume statement", Instr::parse_resume),
+            context("renumber statement", Instr::parse_renumber),
         ))(s)
     }
 
@@ -11,7 +11,7 @@ mod tests {
         for entry in fs::read_dir(basic_programs_dir).expect("Directory not found") {
             let entry = entry.expect("Failed to read entry");
             let path = entry.path();
-            if path.is_file() {
+            if path.extension().is_some_and(|ext| ext == "zx") {
                 let content = fs::read_to_string(&path).expect("Failed to read file");
                 let parsed = parse_file(&content, false);
                 assert!(parsed.is_ok(), "Failed to parse file: {:?}\n {:#?}", path, parsed);
@@ -0,0 +1,40 @@
+use super::{Expr, Instr, Symbol};
+
+/// A numbered program line. Lines of unprefixed files are numbered 10, 20, 30, ...
+#[derive(Debug, PartialEq, Clone)]
+pub struct Line<'a> {
+    pub number: usize,
+    pub instr: Instr<'a>,
+}
+
+/// Index of the line a jump to `number` lands on: that line, or the next one after it if
+/// there is no such line. Jumping past the last line ends the program.
+pub fn find_line(lines: &[Line], number: usize) -> usize {
+    lines
+        .iter()
+        .position(|line| line.number >= number)
+        .unwrap_or(lines.len())
+}
+
+/// Whether a statement is NEXT for the variable `symbol`, including after THEN.
+fn is_next(instr: &Instr, symbol: Symbol) -> bool {
+    match instr {
+        Instr::Next(Expr::Ident(next)) => next.symbol() == symbol,
+        Instr::IfThen(_, then) => is_next(then, symbol),
+        _ => false,
+    }
+}
+
+/// The indices of the line and statement of the first NEXT for the variable `symbol` after the
+/// statement at `pc` and `stmt`, which is where a FOR loop that doesn't run at all skips to.
+/// Like the Spectrum, it finds a NEXT after THEN too, and skips to what follows it whatever the
+/// condition. The program's names have to be interned.
+pub fn find_next(lines: &[Line], symbol: Symbol, pc: usize, stmt: usize) -> Option<(usize, usize)> {
+    let statements = lines.iter().enumerate().skip(pc).flat_map(|(pc, line)| {
+        line.instr.statements().iter().enumerate().map(move |(stmt, instr)| (pc, stmt, instr))
+    });
+    statements
+        .skip(stmt + 1)
+        .find(|(_, _, instr)| is_next(instr, symbol))
+        .map(|(pc, stmt, _)| (pc, stmt))
+}
@@ -1,22 +1,66 @@
+// The nom parsers return results that borrow from their input, which `ParseResult<Instr>`
+// leaves implicit, as nom's own signatures do
+#![allow(mismatched_lifetime_syntaxes)]
+mod diagnostic;
 mod expr;
 mod instr;
 mod integration_tests;
+mod line;
 mod parse_tools;
+mod pretty;
 mod parser_tests;
-use parse_tools::NomErr;
-mod lower;
+mod pretty_tests;
+use nom::character::complete::digit1;
+pub use parse_tools::NomErr;
+mod symbol;
 
-pub use lower::LowerCase;
+pub use diagnostic::ParseDiagnostic;
+pub use symbol::{Ident, Symbol, Symbols};
 pub use expr::Expr;
-pub use instr::Instr;
+pub use instr::{InputItem, Instr, TapeCommand, TapeData};
+pub use line::{find_line, find_next, Line};
 
-pub fn parse_file(file: &str, prefixed: bool) -> Result<Vec<Instr>, nom::Err<NomErr>> {
-    let parse_fn = if prefixed {
-        Instr::parse_prefixed
+fn parse_line(line: &str, index: usize, prefixed: bool) -> Result<Line<'_>, nom::Err<NomErr<'_>>> {
+    if prefixed {
+        Instr::parse_prefixed(line).map(|(_, (number, instr))| Line { number, instr })
     } else {
-        Instr::parse
-    };
-    file.lines() 
-        .map(|line| parse_fn(line).map(|(_, res)| res))
+        Instr::parse(line).map(|(_, instr)| Line {
+            number: (index + 1) * 10,
+            instr,
+        })
+    }
+}
+
+pub fn parse_file(file: &str, prefixed: bool) -> Result<Vec<Line<'_>>, nom::Err<NomErr<'_>>> {
+    file.lines()
+        .enumerate()
+        .map(|(i, line)| parse_line(line, i, prefixed))
         .collect()
 }
+
+/// Parses every line, even after errors. Lines that fail to parse are kept in
+/// the program as `Instr::SyntaxError`, so line numbers (and so GO TO targets)
+/// are unchanged, and running into one reports the error like the Spectrum would.
+pub fn parse_file_recovering(file: &str, prefixed: bool) -> (Vec<Line<'_>>, Vec<ParseDiagnostic>) {
+    let mut diagnostics = vec![];
+    let mut lines: Vec<Line> = vec![];
+    for (i, line) in file.lines().enumerate() {
+        match parse_line(line, i, prefixed) {
+            Ok(res) => lines.push(res),
+            Err(e) => {
+                diagnostics.push(ParseDiagnostic::new(i + 1, line, e));
+                // Keep the line number if there is one, so GO TO still finds the line
+                let (number, text) = match (prefixed, digit1::<_, NomErr>(line)) {
+                    (false, _) => ((i + 1) * 10, line),
+                    (true, Ok((text, digits))) => (digits.parse().unwrap_or(0), text.trim_start()),
+                    (true, Err(_)) => (lines.last().map_or(0, |l| l.number), line),
+                };
+                lines.push(Line {
+                    number,
+                    instr: Instr::SyntaxError(text),
+                });
+            }
+        }
+    }
+    (lines, diagnostics)
+}
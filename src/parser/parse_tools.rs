@@ -5,7 +5,7 @@ use nom::{
     IResult,
 };
 
-use super::{Expr, LowerCase};
+use super::{Expr, Ident};
 
 pub type NomErr<'a> = VerboseError<&'a str>;
 pub type ParseResult<'a, T> = IResult<&'a str, T, NomErr<'a>>;
@@ -18,5 +18,5 @@ where
 }
 
 pub fn ident(s: &str) -> Expr {
-    Expr::Ident(LowerCase(s))
+    Expr::Ident(Ident::new(s))
 }
\ No newline at end of file
@@ -1,8 +1,9 @@
 #[cfg(test)]
 mod tests {
     use crate::parser::{
+        parse_file, parse_file_recovering,
         parse_tools::{ident, NomErr},
-        Expr, Instr,
+        Expr, Ident, InputItem, Instr, TapeCommand, TapeData,
     };
 
     fn success<'a, T>(instr: T) -> Result<(&'a str, T), nom::Err<NomErr<'a>>> {
@@ -144,18 +145,59 @@ mod tests {
                 Box::new(Instr::Print(Some(Expr::Int(2)), vec![], None))
             ))
         );
+        // The statements after the first one are the line's, for a false IF to skip
+        assert_eq!(
+            Instr::parse("IF 1 THEN PRINT 2: GO TO 10"),
+            success(Instr::Multi(vec![
+                Instr::IfThen(Expr::Int(1), Box::new(Instr::Print(Some(Expr::Int(2)), vec![], None))),
+                Instr::Goto(10),
+            ]))
+        );
     }
 
     #[test]
     fn test_input() {
         assert_eq!(
             Instr::parse("INPUT x"),
-            success(Instr::Input(None, ident("x")))
+            success(Instr::Input(vec![InputItem::Var(Ident::new("x"))]))
         );
         assert_eq!(
-            Instr::parse("INPUT x, 42"),
-            success(Instr::Input(Some(ident("x")), Expr::Int(42)))
+            Instr::parse("INPUT \"Name? \";n$;\" Age? \";a"),
+            success(Instr::Input(vec![
+                InputItem::Prompt(Expr::String("Name? ")),
+                InputItem::Separator(';'),
+                InputItem::Var(Ident::new("n$")),
+                InputItem::Separator(';'),
+                InputItem::Prompt(Expr::String(" Age? ")),
+                InputItem::Separator(';'),
+                InputItem::Var(Ident::new("a")),
+            ]))
+        );
+        // A variable in brackets is printed, not read
+        assert_eq!(
+            Instr::parse("INPUT AT 1,0;(x),LINE a$"),
+            success(Instr::Input(vec![
+                InputItem::At(Expr::Int(1), Expr::Int(0)),
+                InputItem::Separator(';'),
+                InputItem::Prompt(ident("x")),
+                InputItem::Separator(','),
+                InputItem::Line(Ident::new("a$")),
+            ]))
         );
+        assert_eq!(
+            Instr::parse("INPUT 1,F;(a+1)"),
+            success(Instr::Input(vec![
+                InputItem::Prompt(Expr::Int(1)),
+                InputItem::Separator(','),
+                InputItem::Var(Ident::new("F")),
+                InputItem::Separator(';'),
+                InputItem::Prompt(Expr::Add(Box::new(ident("a")), Box::new(Expr::Int(1)))),
+            ]))
+        );
+        // Something that starts with a letter is just a variable
+        for invalid in ["INPUT a+1", "INPUT a=1", "INPUT LINE a", "INPUT \"x\" a"] {
+            assert!(Instr::parse(invalid).is_err(), "{}", invalid);
+        }
     }
 
     #[test]
@@ -190,4 +232,113 @@ mod tests {
             ))
         );
     }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(
+            Instr::parse("LET my total 2=5"),
+            success(Instr::Assign(ident("my total 2"), Expr::Int(5)))
+        );
+        assert_eq!(Expr::parse("a1 b"), success(ident("a1 b")));
+        // A keyword ends a name
+        assert_eq!(
+            Instr::parse("FOR i = 1 TO last one STEP big jump"),
+            success(Instr::For(ident("i"), Expr::Int(1), ident("last one"), ident("big jump")))
+        );
+        assert_eq!(
+            Instr::parse("IF total THEN PRINT total"),
+            success(Instr::IfThen(
+                ident("total"),
+                Box::new(Instr::Print(Some(ident("total")), vec![], None))
+            ))
+        );
+        assert!(Instr::parse("LET 2a=1").is_err());
+    }
+
+    #[test]
+    fn test_recovering() {
+        let file = "LET x = 1\nLET y =\nPRINT x\nFROB\nPRINT y";
+        assert!(parse_file(file, false).is_err());
+
+        let (instrs, diagnostics) = parse_file_recovering(file, false);
+        assert_eq!(instrs.len(), 5);
+        assert_eq!(instrs[1].instr, Instr::SyntaxError("LET y ="));
+        assert_eq!(instrs[3].instr, Instr::SyntaxError("FROB"));
+        assert_eq!(
+            diagnostics.iter().map(|d| d.line).collect::<Vec<_>>(),
+            vec![2, 4]
+        );
+        assert_eq!(instrs[4].number, 50);
+        assert_eq!(
+            instrs[4].instr,
+            Instr::Print(Some(ident("y")), vec![], None)
+        );
+    }
+
+    #[test]
+    fn test_jumps() {
+        assert_eq!(Instr::parse("GO TO 10"), success(Instr::Goto(10)));
+        assert_eq!(Instr::parse("GO SUB 100"), success(Instr::Gosub(100)));
+        assert_eq!(Instr::parse("RETURN"), success(Instr::Return));
+        assert_eq!(Instr::parse("STOP"), success(Instr::Stop));
+        assert!(Instr::parse("GO TO 99999999999999999999").is_err());
+
+        let (lines, diagnostics) = parse_file_recovering("10 GO TO 99999999999999999999", true);
+        assert_eq!(
+            lines[0].instr,
+            Instr::SyntaxError("GO TO 99999999999999999999")
+        );
+        assert_eq!(diagnostics.len(), 1);
+    }
+
+    #[test]
+    fn test_prefixed() {
+        assert_eq!(
+            Instr::parse_prefixed("120 GO SUB 10"),
+            success((120, Instr::Gosub(10)))
+        );
+        let lines = parse_file("5 PRINT\n20 STOP", true).unwrap();
+        assert_eq!(
+            lines.iter().map(|l| l.number).collect::<Vec<_>>(),
+            vec![5, 20]
+        );
+    }
+
+    #[test]
+    fn test_tape() {
+        let name = Expr::String("game");
+        assert_eq!(
+            Instr::parse("SAVE \"game\" LINE 10"),
+            success(Instr::Tape(TapeCommand::Save, name.clone(), TapeData::Program(Some(10))))
+        );
+        assert_eq!(
+            Instr::parse("load \"\""),
+            success(Instr::Tape(TapeCommand::Load, Expr::String(""), TapeData::Program(None)))
+        );
+        assert_eq!(
+            Instr::parse("SAVE \"game\" CODE 32768, n*2"),
+            success(Instr::Tape(
+                TapeCommand::Save,
+                name.clone(),
+                TapeData::Code(
+                    Some(Expr::Int(32768)),
+                    Some(Expr::Mul(Box::new(ident("n")), Box::new(Expr::Int(2))))
+                )
+            ))
+        );
+        assert_eq!(
+            Instr::parse("VERIFY \"game\" DATA a$()"),
+            success(Instr::Tape(TapeCommand::Verify, name.clone(), TapeData::Data("a$")))
+        );
+        assert_eq!(
+            Instr::parse("LOAD \"game\" SCREEN$: STOP"),
+            success(Instr::Multi(vec![
+                Instr::Tape(TapeCommand::Load, name, TapeData::Screen),
+                Instr::Stop
+            ]))
+        );
+        for invalid in ["LOAD \"x\" LINE 10", "SAVE \"x\" CODE 1", "MERGE \"x\" CODE", "SAVE"] {
+            assert!(Instr::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
 }
@@ -0,0 +1,171 @@
+use std::fmt::{Display, Formatter, Result};
+
+use super::{Expr, InputItem, Instr, Line, TapeCommand, TapeData};
+
+impl Expr<'_> {
+    /// How tightly the expression binds, used to decide where brackets are needed.
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Ident(_) | Expr::Int(_) | Expr::String(_) | Expr::Err | Expr::Erl => 3,
+            Expr::Mul(_, _) | Expr::Div(_, _) => 2,
+            Expr::Add(_, _) | Expr::Sub(_, _) => 1,
+            Expr::Gt(_, _)
+            | Expr::Lt(_, _)
+            | Expr::Eq(_, _)
+            | Expr::Ge(_, _)
+            | Expr::Le(_, _)
+            | Expr::Ne(_, _) => 0,
+        }
+    }
+
+    fn fmt_operand(&self, f: &mut Formatter, bracket: bool) -> Result {
+        if bracket {
+            write!(f, "({})", self)
+        } else {
+            write!(f, "{}", self)
+        }
+    }
+}
+
+impl Display for Expr<'_> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let (lhs, op, rhs) = match self {
+            Expr::Ident(ident) => return write!(f, "{}", ident),
+            Expr::Int(i) => return write!(f, "{}", i),
+            Expr::String(s) => return write!(f, "\"{}\"", s),
+            Expr::Err => return write!(f, "ERR"),
+            Expr::Erl => return write!(f, "ERL"),
+            Expr::Add(lhs, rhs) => (lhs, "+", rhs),
+            Expr::Sub(lhs, rhs) => (lhs, "-", rhs),
+            Expr::Mul(lhs, rhs) => (lhs, "*", rhs),
+            Expr::Div(lhs, rhs) => (lhs, "/", rhs),
+            Expr::Gt(lhs, rhs) => (lhs, ">", rhs),
+            Expr::Lt(lhs, rhs) => (lhs, "<", rhs),
+            Expr::Eq(lhs, rhs) => (lhs, "=", rhs),
+            Expr::Ge(lhs, rhs) => (lhs, ">=", rhs),
+            Expr::Le(lhs, rhs) => (lhs, "<=", rhs),
+            Expr::Ne(lhs, rhs) => (lhs, "<>", rhs),
+        };
+        // All operators are left associative, so the right operand also needs
+        // brackets at equal precedence: a-(b-c)
+        lhs.fmt_operand(f, lhs.precedence() < self.precedence())?;
+        write!(f, "{}", op)?;
+        rhs.fmt_operand(f, rhs.precedence() <= self.precedence())
+    }
+}
+
+impl Display for Instr<'_> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self {
+            Instr::Print(None, _, _) => write!(f, "PRINT"),
+            Instr::Print(Some(first), rest, last) => {
+                write!(f, "PRINT {}", first)?;
+                for (sep, expr) in rest {
+                    write!(f, "{}{}", sep, expr)?;
+                }
+                match last {
+                    Some(last) => write!(f, "{}", last),
+                    None => Ok(()),
+                }
+            }
+            Instr::Assign(ident, expr) => write!(f, "LET {}={}", ident, expr),
+            Instr::Input(items) => {
+                write!(f, "INPUT ")?;
+                items.iter().try_for_each(|item| write!(f, "{}", item))
+            }
+            Instr::Rem("") => write!(f, "REM"),
+            Instr::Rem(text) => write!(f, "REM {}", text),
+            Instr::Goto(number) => write!(f, "GO TO {}", number),
+            Instr::Gosub(number) => write!(f, "GO SUB {}", number),
+            Instr::Return => write!(f, "RETURN"),
+            Instr::Stop => write!(f, "STOP"),
+            Instr::Cls => write!(f, "CLS"),
+            Instr::List(None) => write!(f, "LIST"),
+            Instr::List(Some(number)) => write!(f, "LIST {}", number),
+            Instr::Run(None) => write!(f, "RUN"),
+            Instr::Run(Some(number)) => write!(f, "RUN {}", number),
+            Instr::Clear => write!(f, "CLEAR"),
+            Instr::Continue => write!(f, "CONTINUE"),
+            Instr::IfThen(cond, instr) => write!(f, "IF {} THEN {}", cond, instr),
+            Instr::Multi(instrs) => {
+                for (i, instr) in instrs.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ": ")?;
+                    }
+                    write!(f, "{}", instr)?;
+                }
+                Ok(())
+            }
+            Instr::For(ident, start, end, Expr::Int(1)) => {
+                write!(f, "FOR {}={} TO {}", ident, start, end)
+            }
+            Instr::For(ident, start, end, step) => {
+                write!(f, "FOR {}={} TO {} STEP {}", ident, start, end, step)
+            }
+            Instr::Next(ident) => write!(f, "NEXT {}", ident),
+            Instr::Tape(command, name, data) => write!(f, "{} {}{}", command, name, data),
+            Instr::OnErrorGoto(number) => write!(f, "ON ERROR GO TO {}", number),
+            Instr::Resume(false) => write!(f, "RESUME"),
+            Instr::Resume(true) => write!(f, "RESUME NEXT"),
+            Instr::Renumber(start, step) => {
+                write!(f, "RENUMBER")?;
+                if let Some(start) = start {
+                    write!(f, " {}", start)?;
+                }
+                match step {
+                    Some(step) => write!(f, ",{}", step),
+                    None => Ok(()),
+                }
+            }
+            Instr::SyntaxError(text) => write!(f, "{}", text),
+        }
+    }
+}
+
+impl Display for TapeCommand {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let keyword = match self {
+            TapeCommand::Save => "SAVE",
+            TapeCommand::Load => "LOAD",
+            TapeCommand::Verify => "VERIFY",
+            TapeCommand::Merge => "MERGE",
+        };
+        write!(f, "{}", keyword)
+    }
+}
+
+impl Display for TapeData<'_> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self {
+            TapeData::Program(None) => Ok(()),
+            TapeData::Program(Some(number)) => write!(f, " LINE {}", number),
+            TapeData::Code(None, _) => write!(f, " CODE"),
+            TapeData::Code(Some(start), None) => write!(f, " CODE {}", start),
+            TapeData::Code(Some(start), Some(length)) => write!(f, " CODE {},{}", start, length),
+            TapeData::Data(name) => write!(f, " DATA {}()", name),
+            TapeData::Screen => write!(f, " SCREEN$"),
+        }
+    }
+}
+
+impl Display for InputItem<'_> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self {
+            InputItem::Prompt(expr) => match expr.to_string() {
+                // Without brackets, it would be a variable to read into
+                text if text.starts_with(|c: char| c.is_ascii_alphabetic()) => write!(f, "({})", text),
+                text => write!(f, "{}", text),
+            },
+            InputItem::Var(ident) => write!(f, "{}", ident),
+            InputItem::Line(ident) => write!(f, "LINE {}", ident),
+            InputItem::At(line, column) => write!(f, "AT {},{}", line, column),
+            InputItem::Separator(separator) => write!(f, "{}", separator),
+        }
+    }
+}
+
+impl Display for Line<'_> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{} {}", self.number, self.instr)
+    }
+}
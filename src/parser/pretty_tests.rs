@@ -0,0 +1,162 @@
+#[cfg(test)]
+mod tests {
+    use crate::parser::{parse_file, parse_tools::ident, Expr, Ident, InputItem, Instr};
+    use proptest::prelude::*;
+    use std::fs;
+    use std::path::Path;
+
+    fn expr_strategy() -> impl Strategy<Value = Expr<'static>> {
+        let leaf = prop_oneof![
+            prop::sample::select(vec!["a", "b", "x", "total"]).prop_map(ident),
+            (-1000i64..1000).prop_map(Expr::Int),
+            prop::sample::select(vec!["", "hello", "deg F"]).prop_map(Expr::String),
+            prop::sample::select(vec![Expr::Err, Expr::Erl]),
+        ];
+        leaf.prop_recursive(4, 32, 2, |inner| {
+            (
+                prop::sample::select(vec!["+", "-", "*", "/", ">", "<", "=", ">=", "<=", "<>"]),
+                inner.clone(),
+                inner,
+            )
+                .prop_map(|(op, lhs, rhs)| {
+                    let fun = match op {
+                        "+" => Expr::Add,
+                        "-" => Expr::Sub,
+                        "*" => Expr::Mul,
+                        "/" => Expr::Div,
+                        ">" => Expr::Gt,
+                        "<" => Expr::Lt,
+                        "=" => Expr::Eq,
+                        ">=" => Expr::Ge,
+                        "<=" => Expr::Le,
+                        _ => Expr::Ne,
+                    };
+                    fun(Box::new(lhs), Box::new(rhs))
+                })
+        })
+    }
+
+    /// Some of what INPUT takes, each followed by a separator, which items need between them.
+    fn input_item_strategy() -> impl Strategy<Value = Vec<InputItem<'static>>> {
+        let item = prop_oneof![
+            expr_strategy().prop_map(InputItem::Prompt),
+            prop::sample::select(vec!["a", "b", "x", "total", "n$"]).prop_map(|name| InputItem::Var(Ident::new(name))),
+            prop::sample::select(vec!["a$", "n$"]).prop_map(|name| InputItem::Line(Ident::new(name))),
+            (expr_strategy(), expr_strategy()).prop_map(|(line, column)| InputItem::At(line, column)),
+        ];
+        (item, prop::sample::select(vec![';', ',', '\''])).prop_map(|(item, separator)| vec![item, InputItem::Separator(separator)])
+    }
+
+    fn instr_strategy() -> impl Strategy<Value = Instr<'static>> {
+        let var = prop::sample::select(vec!["a", "b", "x", "total"]).prop_map(ident);
+        let simple = prop_oneof![
+            (
+                prop::option::of(expr_strategy()),
+                prop::collection::vec((prop::sample::select(vec![',', ';']), expr_strategy()), 0..3),
+                prop::option::of(prop::sample::select(vec![',', ';'])),
+            )
+                .prop_map(|(first, rest, last)| match first {
+                    Some(first) => Instr::Print(Some(first), rest, last),
+                    None => Instr::Print(None, vec![], None),
+                }),
+            (var.clone(), expr_strategy()).prop_map(|(var, expr)| Instr::Assign(var, expr)),
+            prop::collection::vec(input_item_strategy(), 1..4).prop_map(|items| Instr::Input(items.concat())),
+            (0usize..10000).prop_map(Instr::Goto),
+            (0usize..10000).prop_map(Instr::Gosub),
+            prop::option::of(0usize..10000).prop_map(Instr::List),
+            prop::option::of(0usize..10000).prop_map(Instr::Run),
+            Just(Instr::Clear),
+            Just(Instr::Continue),
+            Just(Instr::Return),
+            Just(Instr::Stop),
+            Just(Instr::Cls),
+            (prop::option::of(1usize..10000), prop::option::of(1usize..10000))
+                .prop_map(|(start, step)| Instr::Renumber(start, step)),
+            prop::sample::select(vec!["", "hello world", " indented"]).prop_map(Instr::Rem),
+            (
+                prop::sample::select(vec!["i", "n"]).prop_map(ident),
+                expr_strategy(),
+                expr_strategy(),
+                expr_strategy()
+            )
+                .prop_map(|(var, start, end, step)| Instr::For(var, start, end, step)),
+            prop::sample::select(vec!["i", "n"]).prop_map(|v| Instr::Next(ident(v))),
+        ];
+        // IF holds just the statement after THEN. The rest of the line comes after the IF.
+        let statement = prop_oneof![
+            simple.clone(),
+            (expr_strategy(), simple).prop_map(|(cond, instr)| Instr::IfThen(cond, Box::new(instr))),
+        ];
+        // REM swallows the rest of the line, so it can only come last
+        let ends_line = |instr: &Instr| match instr {
+            Instr::IfThen(_, then) => matches!(**then, Instr::Rem(_)),
+            instr => matches!(instr, Instr::Rem(_)),
+        };
+        let multi = prop::collection::vec(
+            statement.clone().prop_filter("REM ends the line", move |i| !ends_line(i)),
+            1..3,
+        );
+        (multi, prop::option::of(statement)).prop_map(|(mut instrs, last)| {
+            instrs.extend(last);
+            match instrs.len() {
+                1 => instrs.remove(0),
+                _ => Instr::Multi(instrs),
+            }
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn test_expr_round_trip(expr in expr_strategy()) {
+            let source = expr.to_string();
+            prop_assert_eq!(Expr::parse(&source), Ok(("", expr)), "{}", source);
+        }
+
+        #[test]
+        fn test_instr_round_trip(instr in instr_strategy()) {
+            let source = instr.to_string();
+            prop_assert_eq!(Instr::parse(&source), Ok(("", instr)), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_minimal_brackets() {
+        for source in ["1+2*3", "(1+2)*3", "a-(b-c)", "a-b-c", "a<b=(c>d)", "a*b/c"] {
+            let (_, expr) = Expr::parse(source).unwrap();
+            assert_eq!(expr.to_string(), source);
+        }
+        let (_, expr) = Expr::parse("((a) + (2 * b))").unwrap();
+        assert_eq!(expr.to_string(), "a+2*b");
+    }
+
+    #[test]
+    fn test_canonical_listing() {
+        let (_, instr) = Instr::parse("if x>=1 then print \"big\" ; x : go to 10").unwrap();
+        assert_eq!(instr.to_string(), "IF x>=1 THEN PRINT \"big\";x: GO TO 10");
+        let (_, instr) = Instr::parse("for i = 1 to 10 step 1").unwrap();
+        assert_eq!(instr.to_string(), "FOR i=1 TO 10");
+        let (_, instr) = Instr::parse("save \"x\" code 0 , 10 : load \"\"screen$").unwrap();
+        assert_eq!(instr.to_string(), "SAVE \"x\" CODE 0,10: LOAD \"\" SCREEN$");
+        let (_, instr) = Instr::parse("on error go to 100: resume: resume next").unwrap();
+        assert_eq!(instr.to_string(), "ON ERROR GO TO 100: RESUME: RESUME NEXT");
+        let (_, instr) = Instr::parse("renumber: renumber 100: renumber , 5").unwrap();
+        assert_eq!(instr.to_string(), "RENUMBER: RENUMBER 100: RENUMBER,5");
+    }
+
+    #[test]
+    fn test_programs_round_trip() {
+        for (dir, prefixed) in [("basic", false), ("intermediate", true)] {
+            for entry in fs::read_dir(Path::new("programs").join(dir)).expect("Directory not found") {
+                let path = entry.expect("Failed to read entry").path();
+                if path.extension().is_none_or(|ext| ext != "zx") {
+                    continue;
+                }
+                let content = fs::read_to_string(&path).expect("Failed to read file");
+                let lines = parse_file(&content, prefixed).expect("Failed to parse file");
+                let listing: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+                let listing = listing.join("\n");
+                assert_eq!(parse_file(&listing, true), Ok(lines), "{:?}", path);
+            }
+        }
+    }
+}
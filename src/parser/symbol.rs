@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter, Result};
+use std::hash::{Hash, Hasher};
+
+/// A variable name, numbered by the `Symbols` it was interned in, from 0, so it can index a
+/// vector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    /// What a name is before it's interned, which is no variable.
+    const UNINTERNED: Symbol = Symbol(u32::MAX);
+
+    /// The symbol that indexes a vector at `index`.
+    pub fn new(index: usize) -> Self {
+        Symbol(index as u32)
+    }
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// The names of a program's variables, interned. Names that differ only in case or spaces,
+/// like `My Total` and `mytotal`, are the same symbol. Each way a name is written is kept
+/// too, so looking one up again doesn't have to lower it.
+#[derive(Debug, Default, Clone)]
+pub struct Symbols {
+    names: Vec<String>,
+    symbols: HashMap<String, Symbol>,
+}
+
+impl Symbols {
+    /// The symbol for a name, if it's been interned.
+    pub fn find(&self, name: &str) -> Option<Symbol> {
+        match self.symbols.get(name) {
+            Some(symbol) => Some(*symbol),
+            None => self.symbols.get(&Ident::new(name).plain()).copied(),
+        }
+    }
+
+    /// The symbol for a name, adding it if it's new.
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(symbol) = self.symbols.get(name) {
+            return *symbol;
+        }
+        let plain = Ident::new(name).plain();
+        let symbol = match self.symbols.get(&plain) {
+            Some(symbol) => *symbol,
+            None => {
+                let symbol = Symbol(self.names.len() as u32);
+                self.names.push(plain.clone());
+                self.symbols.insert(plain, symbol);
+                symbol
+            }
+        };
+        self.symbols.insert(name.to_string(), symbol);
+        symbol
+    }
+
+    /// The name as the Spectrum stores it, in lower case without spaces.
+    pub fn name(&self, symbol: Symbol) -> &str {
+        &self.names[symbol.index()]
+    }
+
+    /// The names, in the order of their symbols.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.names.iter().map(String::as_str)
+    }
+
+    /// How many names have been interned.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+}
+
+/// A variable's name as written in the program, and its symbol once it's interned in the
+/// `Symbols` of the program it runs in. Idents are equal if they're written the same, like
+/// the listings they're in; whether they name the same variable is whether their symbols are.
+#[derive(Clone, Copy)]
+pub struct Ident<'a> {
+    pub name: &'a str,
+    symbol: Symbol,
+}
+
+impl<'a> Ident<'a> {
+    pub fn new(name: &'a str) -> Self {
+        Ident {
+            name,
+            symbol: Symbol::UNINTERNED,
+        }
+    }
+
+    /// The symbol it was interned as, which indexes the variables.
+    pub fn symbol(&self) -> Symbol {
+        self.symbol
+    }
+
+    /// Interns the name, giving it its symbol in `symbols`.
+    pub fn intern(&mut self, symbols: &mut Symbols) {
+        self.symbol = symbols.intern(self.name);
+    }
+
+    /// Whether it names a string variable, like `a$`, rather than a number.
+    pub fn is_string(&self) -> bool {
+        self.name.ends_with('$')
+    }
+
+    /// How the Spectrum stores the name: in lower case, without spaces.
+    pub fn plain(&self) -> String {
+        self.name.chars().filter(|c| *c != ' ').map(|c| c.to_ascii_lowercase()).collect()
+    }
+}
+
+impl PartialEq for Ident<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Eq for Ident<'_> {}
+
+impl Hash for Ident<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+    }
+}
+
+impl Debug for Ident<'_> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "Ident({:?})", self.name)
+    }
+}
+
+impl Display for Ident<'_> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Ident, Symbols};
+
+    #[test]
+    fn test_symbols() {
+        let mut symbols = Symbols::default();
+        let total = symbols.intern("My Total 2");
+        assert_eq!(symbols.intern("mytotal2"), total);
+        assert_ne!(symbols.intern("total"), total);
+        assert_eq!(symbols.name(total), "mytotal2");
+        assert_eq!(symbols.find("MYTOTAL 2"), Some(total));
+        assert_eq!(symbols.find("never used"), None);
+        assert_ne!(symbols.intern("a$"), symbols.intern("a"));
+        assert_eq!(symbols.len(), 4);
+
+        let (mut upper, mut lower) = (Ident::new("A"), Ident::new("a"));
+        upper.intern(&mut symbols);
+        lower.intern(&mut symbols);
+        assert_eq!(upper.symbol(), lower.symbol());
+        assert_ne!(upper, lower);
+        assert_eq!(Ident::new("My Total 2").plain(), "mytotal2");
+        assert_eq!(Ident::new("A b").to_string(), "A b");
+        assert!(Ident::new("N$").is_string() && !Ident::new("n").is_string());
+    }
+}
@@ -0,0 +1,210 @@
+//! Measures where a program spends its time: how often each line and kind of statement runs
+//! and for how long, which FOR loops it spends longest in, and the time under each GO SUB.
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use crate::parser::find_next;
+use crate::{Interpreter, Status};
+
+/// How many times something ran, and for how long altogether.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Stat {
+    pub count: u64,
+    pub time: Duration,
+}
+
+impl Stat {
+    fn add(&mut self, time: Duration) {
+        self.count += 1;
+        self.time += time;
+    }
+}
+
+/// A statement about to run, and where it is in the program.
+struct Sample {
+    line: usize,
+    keyword: &'static str,
+    /// The lines of the GO SUBs it's in, outermost first.
+    calls: Vec<usize>,
+    /// The FOR loops it's in, by their line and variable: the ones still running that it, or a
+    /// GO SUB it's under, is between the FOR and the NEXT of.
+    loops: Vec<(usize, String)>,
+}
+
+impl Sample {
+    fn of(interpreter: &Interpreter) -> Option<Sample> {
+        let (line, _) = interpreter.position()?;
+        let lines = interpreter.lines();
+        let state = interpreter.state();
+        let number = |pc: usize| lines.get(pc).map_or(0, |line| line.number);
+        let running = state.vars.loops().filter(|&(symbol, for_loop)| {
+            state.vars.get(symbol).is_some_and(|value| for_loop.running(value))
+        });
+        let loops = running.filter_map(|(symbol, for_loop)| {
+            let start = (for_loop.pc, for_loop.stmt + 1);
+            let end = find_next(lines, symbol, for_loop.pc, for_loop.stmt)?;
+            let inside = |at: &(usize, usize)| start <= *at && *at <= end;
+            let inside = inside(&(state.pc, state.stmt)) || state.gosub_stack.iter().any(inside);
+            inside.then(|| (number(for_loop.pc), state.vars.name(symbol).to_string()))
+        });
+        Some(Sample {
+            line,
+            keyword: interpreter.statement()?.keyword(),
+            calls: state.gosub_stack.iter().map(|&(pc, _)| number(pc)).collect(),
+            loops: loops.collect(),
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Profile {
+    pub lines: HashMap<usize, Stat>,
+    pub keywords: HashMap<&'static str, Stat>,
+    /// Everything run inside each FOR loop, including inner loops.
+    pub loops: HashMap<(usize, String), Stat>,
+    /// The line run, after the lines of the GO SUBs it was called through.
+    pub stacks: HashMap<Vec<usize>, Stat>,
+}
+
+/// Sorts with the most time first.
+fn by_time<K: Ord + Clone>(stats: &HashMap<K, Stat>) -> Vec<(K, Stat)> {
+    let mut sorted: Vec<_> = stats.iter().map(|(key, stat)| (key.clone(), *stat)).collect();
+    sorted.sort_by(|(a, a_stat), (b, b_stat)| b_stat.time.cmp(&a_stat.time).then(a.cmp(b)));
+    sorted
+}
+
+fn millis(time: Duration) -> f64 {
+    time.as_secs_f64() * 1000.0
+}
+
+impl Profile {
+    fn record(&mut self, sample: &Sample, time: Duration) {
+        self.lines.entry(sample.line).or_default().add(time);
+        self.keywords.entry(sample.keyword).or_default().add(time);
+        for key in &sample.loops {
+            self.loops.entry(key.clone()).or_default().add(time);
+        }
+        let mut stack = sample.calls.clone();
+        stack.push(sample.line);
+        self.stacks.entry(stack).or_default().add(time);
+    }
+
+    /// Runs the program until it finishes or stops, timing each statement.
+    pub fn run(&mut self, interpreter: &mut Interpreter) -> Status {
+        let mut sample = Sample::of(interpreter);
+        let mut start = Instant::now();
+        let status = interpreter.run_until(|interpreter| {
+            let time = start.elapsed();
+            if let Some(sample) = &sample {
+                self.record(sample, time);
+            }
+            sample = Sample::of(interpreter);
+            start = Instant::now();
+            false
+        });
+        // The statement that finished or stopped the program
+        if let Some(sample) = &sample {
+            self.record(sample, start.elapsed());
+        }
+        status
+    }
+
+    /// A report of lines, kinds of statement and FOR loops, with the most time first.
+    pub fn report(&self) -> String {
+        let total: Duration = self.lines.values().map(|stat| stat.time).sum();
+        let percent = |time: Duration| match total.is_zero() {
+            true => 0.0,
+            false => time.as_secs_f64() * 100.0 / total.as_secs_f64(),
+        };
+        let mut text = String::new();
+        let mut table = |title: &str, rows: Vec<(String, Stat)>| {
+            let _ = writeln!(text, "{:<16} {:>10} {:>12} {:>7}", title, "count", "time (ms)", "time %");
+            for (name, stat) in rows {
+                let _ = writeln!(
+                    text,
+                    "{:<16} {:>10} {:>12.3} {:>6.1}%",
+                    name,
+                    stat.count,
+                    millis(stat.time),
+                    percent(stat.time)
+                );
+            }
+            text.push('\n');
+        };
+        let lines = by_time(&self.lines).into_iter().map(|(line, stat)| (line.to_string(), stat));
+        table("Line", lines.collect());
+        let keywords = by_time(&self.keywords).into_iter().map(|(keyword, stat)| (keyword.to_string(), stat));
+        table("Statement", keywords.collect());
+        let loops = by_time(&self.loops)
+            .into_iter()
+            .map(|((line, var), stat)| (format!("FOR {} at {}", var, line), stat));
+        table("Loop", loops.collect());
+        let _ = writeln!(text, "Total: {:.3} ms", millis(total));
+        text
+    }
+
+    /// The time under each GO SUB path, in microseconds, as folded stacks for flame graph
+    /// tools: `main;30;110 250` is 250us on line 110, in a subroutine called from line 30.
+    pub fn folded(&self) -> String {
+        let mut stacks: Vec<_> = self.stacks.iter().collect();
+        stacks.sort_by_key(|(stack, _)| *stack);
+        let mut text = String::new();
+        for (stack, stat) in stacks {
+            let frames: Vec<_> = stack.iter().map(|line| line.to_string()).collect();
+            let _ = writeln!(text, "main;{} {}", frames.join(";"), stat.time.as_micros());
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Profile;
+    use crate::console::Capture;
+    use crate::parser::parse_file;
+use crate::{Interpreter, Status};
+
+    const PROGRAM: &str = "\
+10 FOR i=1 TO 3
+20 GO SUB 100
+30 NEXT i
+40 STOP
+100 LET x=i*2
+110 RETURN";
+
+    fn profile() -> Profile {
+        let mut interpreter = Interpreter::new(parse_file(PROGRAM, true).unwrap());
+        interpreter.set_console(Box::new(Capture::default()));
+        let mut profile = Profile::default();
+        assert!(matches!(profile.run(&mut interpreter), Status::Stopped(_)));
+        profile
+    }
+
+    #[test]
+    fn test_counts() {
+        let profile = profile();
+        let count = |line| profile.lines[&line].count;
+        let counts: Vec<_> = [10, 20, 30, 40, 100, 110].into_iter().map(count).collect();
+        assert_eq!(counts, [1, 3, 3, 1, 3, 3]);
+        assert_eq!(profile.keywords["GO SUB"].count, 3);
+        assert_eq!(profile.keywords["STOP"].count, 1);
+        // The FOR statement itself runs before its loop starts
+        assert_eq!(profile.loops[&(10, "i".to_string())].count, 12);
+    }
+
+    #[test]
+    fn test_report() {
+        let profile = profile();
+        let report = profile.report();
+        assert!(report.starts_with("Line "), "{}", report);
+        for heading in ["\nStatement ", "\nLoop ", "\nFOR i at 10 ", "\nGO SUB ", "\nTotal: "] {
+            assert!(report.contains(heading), "{:?} isn't in {}", heading, report);
+        }
+
+        let folded = profile.folded();
+        let stacks: Vec<_> = folded.lines().map(|line| line.rsplit_once(' ').unwrap().0).collect();
+        let expected = ["main;10", "main;20", "main;20;100", "main;20;110", "main;30", "main;40"];
+        assert_eq!(stacks, expected);
+    }
+}
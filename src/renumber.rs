@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+
+use anyhow::{ensure, Result};
+
+use crate::exec::ErrorCode;
+use crate::parser::{find_line, Line};
+
+/// The highest line number a program can have.
+const MAX_LINE: usize = 9999;
+
+/// The number line `i` gets when renumbering from `start` in steps of `step`, if it's between 1
+/// and 9999.
+pub(crate) fn line_number(i: usize, start: usize, step: usize) -> Option<usize> {
+    i.checked_mul(step)
+        .and_then(|n| n.checked_add(start))
+        .filter(|n| (1..=MAX_LINE).contains(n))
+}
+
+/// Renumbers the program from `start` in steps of `step`, rewriting every line reference
+/// so that it still lands on the same line. A reference to a missing line points at the
+/// line a jump to it would have landed on, and one past the end stays past the end.
+///
+/// Fails with integer out of range if the new numbers, including those of references past
+/// the end, wouldn't be between 1 and 9999.
+pub fn renumber<'a>(lines: &[Line<'a>], start: usize, step: usize) -> Result<Vec<Line<'a>>> {
+    let out_of_range = || ErrorCode::IntegerOutOfRange.with(format!("RENUMBER {},{}", start, step));
+    ensure!(start > 0 && step > 0, out_of_range());
+    let new_number = |i: usize| line_number(i, start, step).ok_or_else(out_of_range);
+    let mut mapping = HashMap::new();
+    for (i, line) in lines.iter().enumerate() {
+        // A duplicated line number is reached at its first occurrence
+        mapping.entry(line.number).or_insert(new_number(i)?);
+    }
+
+    lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            let mut instr = line.instr.clone();
+            for target in instr.line_refs_mut() {
+                *target = match mapping.get(target) {
+                    Some(number) => *number,
+                    None => new_number(find_line(lines, *target))?,
+                };
+            }
+            Ok(Line {
+                number: new_number(i)?,
+                instr,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::renumber;
+    use crate::exec::ErrorCode;
+    use crate::parser::parse_file;
+
+    fn renumbered(file: &str, start: usize, step: usize) -> Vec<String> {
+        let lines = parse_file(file, true).expect("Failed to parse");
+        renumber(&lines, start, step)
+            .expect("Failed to renumber")
+            .iter()
+            .map(|l| l.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_renumber() {
+        assert_eq!(
+            renumbered("1 PRINT 1\n2 GO SUB 7\n5 GO TO 1\n7 RETURN", 100, 5),
+            vec!["100 PRINT 1", "105 GO SUB 115", "110 GO TO 100", "115 RETURN"]
+        );
+    }
+
+    #[test]
+    fn test_renumber_nested_refs() {
+        assert_eq!(
+            renumbered("3 IF a=1 THEN PRINT a: GO TO 4\n4 STOP", 10, 10),
+            vec!["10 IF a=1 THEN PRINT a: GO TO 20", "20 STOP"]
+        );
+    }
+
+    #[test]
+    fn test_renumber_missing_target() {
+        assert_eq!(
+            renumbered("10 GO TO 15\n20 GO TO 99\n30 STOP", 1, 1),
+            vec!["1 GO TO 2", "2 GO TO 4", "3 STOP"]
+        );
+    }
+
+    #[test]
+    fn test_renumber_out_of_range() {
+        let lines = parse_file("10 PRINT 1\n20 GO TO 10", true).expect("Failed to parse");
+        assert_eq!(renumber(&lines, 9990, 9).unwrap()[1].number, 9999);
+        for (start, step) in [(9990, 10), (0, 10), (10, 0), (usize::MAX, 1), (1, usize::MAX)] {
+            let err = renumber(&lines, start, step).unwrap_err();
+            assert_eq!(err.downcast_ref(), Some(&ErrorCode::IntegerOutOfRange), "{}", err);
+        }
+
+        // A jump past the last line is renumbered to one step after it
+        let lines = parse_file("10 PRINT 1\n20 GO TO 30", true).expect("Failed to parse");
+        assert_eq!(renumber(&lines, 9980, 9).unwrap()[1].to_string(), "9989 GO TO 9998");
+        let err = renumber(&lines, 9990, 9).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&ErrorCode::IntegerOutOfRange), "{}", err);
+    }
+}
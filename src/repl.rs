@@ -0,0 +1,208 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+
+use crate::exec::{execute_immediate, Dialect, State};
+use crate::parser::{parse_file, Instr, Line, NomErr, ParseDiagnostic};
+use crate::source::Sources;
+use crate::tape::Tape;
+
+/// An interactive session, like typing at the Spectrum: numbered lines edit the program,
+/// anything else runs immediately. Variables are kept between commands.
+#[derive(Debug)]
+pub struct Repl<'a> {
+    pub program: BTreeMap<usize, Instr<'a>>,
+    pub(crate) state: State<'a>,
+    /// The program's lines, as typed or loaded.
+    sources: &'a Sources,
+}
+
+fn nonsense(input: &str, e: nom::Err<NomErr>) -> anyhow::Error {
+    anyhow!("C Nonsense in BASIC\n{}", ParseDiagnostic::new(1, input, e).message.trim_end())
+}
+
+impl<'a> Repl<'a> {
+    pub fn new(sources: &'a Sources) -> Self {
+        Repl {
+            program: BTreeMap::new(),
+            state: State {
+                sources: Some(sources),
+                ..State::default()
+            },
+            sources,
+        }
+    }
+
+    /// The tape that LOAD, SAVE, MERGE and VERIFY use.
+    pub fn set_tape(&mut self, tape: Tape) {
+        self.state.tape = Some(tape);
+    }
+
+    pub fn set_dialect(&mut self, dialect: Dialect) {
+        self.state.dialect = dialect;
+    }
+
+    pub fn load(&mut self, file: &str, prefixed: bool) -> Result<()> {
+        let mut lines = parse_file(self.sources.keep(file), prefixed)
+            .map_err(|e| anyhow!("Failed to parse file: {:#?}", e))?;
+        self.state.vars.intern(&mut lines);
+        self.program = lines.into_iter().map(|l| (l.number, l.instr)).collect();
+        Ok(())
+    }
+
+    fn lines(&self) -> Vec<Line<'a>> {
+        self.program
+            .iter()
+            .map(|(number, instr)| Line {
+                number: *number,
+                instr: instr.clone(),
+            })
+            .collect()
+    }
+
+    /// Handles one line of input. Returns whether it was run, rather than stored in the program.
+    pub fn enter(&mut self, input: &str) -> Result<bool> {
+        let input = input.trim();
+        if input.is_empty() {
+            return Ok(false);
+        }
+        if let Ok(number) = input.parse::<usize>() {
+            // A line number on its own deletes the line
+            self.program.remove(&number);
+            return Ok(false);
+        }
+        if input.starts_with(|c: char| c.is_ascii_digit()) {
+            let input = self.sources.keep(input);
+            let (_, (number, mut instr)) = Instr::parse_prefixed(input).map_err(|e| nonsense(input, e))?;
+            self.state.vars.intern_instr(&mut instr);
+            self.program.insert(number, instr);
+            return Ok(false);
+        }
+
+        if input.eq_ignore_ascii_case("new") {
+            let tape = self.state.tape.take();
+            *self = Repl::new(self.sources);
+            self.state.tape = tape;
+        } else {
+            let (_, mut instr) = Instr::parse(input).map_err(|e| nonsense(input, e))?;
+            self.state.vars.intern_instr(&mut instr);
+            // LOAD, MERGE and RENUMBER can change the program
+            let mut lines = self.lines();
+            let result = execute_immediate(&instr, &mut lines, &mut self.state);
+            self.program = lines.into_iter().map(|l| (l.number, l.instr)).collect();
+            result?;
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Repl;
+    use crate::source::Sources;
+    use crate::exec::{ErrorCode, Report};
+    use crate::parser::Instr;
+    use crate::tape::Tape;
+
+    fn var(repl: &Repl, name: &str) -> Option<i64> {
+        repl.state.vars.get(repl.state.vars.symbol(name)?)
+    }
+
+    #[test]
+    fn test_editing() {
+        let sources = Sources::default();
+        let mut repl = Repl::new(&sources);
+        for line in ["10 LET a=1", "20 LET b=2", "15 LET c=3", "20 LET b=4", "10"] {
+            assert!(!repl.enter(line).unwrap());
+        }
+        assert_eq!(repl.program.keys().copied().collect::<Vec<_>>(), vec![15, 20]);
+        assert!(repl.enter("RENUMBER 100,5").unwrap());
+        assert_eq!(repl.program.keys().copied().collect::<Vec<_>>(), vec![100, 105]);
+        assert!(repl.enter("NEW").unwrap());
+        assert!(repl.program.is_empty());
+        assert!(repl.enter("30 LET oops =").is_err());
+    }
+
+    #[test]
+    fn test_immediate_mode() {
+        let sources = Sources::default();
+        let mut repl = Repl::new(&sources);
+        repl.enter("LET a=5").unwrap();
+        repl.enter("10 LET b=a*2").unwrap();
+        repl.enter("GO TO 10").unwrap();
+        assert_eq!(var(&repl, "b"), Some(10));
+        // RUN clears the variables first
+        assert!(repl.enter("RUN").is_err());
+        repl.enter("LET a=1").unwrap();
+        repl.enter("CONTINUE").unwrap();
+        assert_eq!(var(&repl, "b"), Some(2));
+        repl.enter("CLEAR").unwrap();
+        assert_eq!(var(&repl, "a"), None);
+        assert_eq!(repl.program[&10], Instr::parse("LET b=a*2").unwrap().1);
+    }
+
+    #[test]
+    fn test_stop_and_continue() {
+        let sources = Sources::default();
+        let mut repl = Repl::new(&sources);
+        repl.enter("10 LET a=1: STOP: LET a=2").unwrap();
+        repl.enter("20 GO SUB 100: LET b=3").unwrap();
+        repl.enter("30 STOP").unwrap();
+        repl.enter("100 LET c=4: RETURN").unwrap();
+        let err = repl.enter("RUN").unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<Report>(),
+            Some(&Report {
+                code: ErrorCode::Stop,
+                line: 10,
+                statement: 2,
+                detail: None
+            })
+        );
+        assert_eq!(err.to_string(), "9 STOP statement, 10:2");
+        assert_eq!(var(&repl, "a"), Some(1));
+        let err = repl.enter("CONTINUE").unwrap_err();
+        assert_eq!(err.to_string(), "9 STOP statement, 30:1");
+        assert_eq!((var(&repl, "a"), var(&repl, "b")), (Some(2), Some(3)));
+        let err = repl.enter("RETURN").unwrap_err();
+        assert_eq!(err.to_string(), "7 RETURN without GO SUB, 0:1");
+    }
+
+    #[test]
+    fn test_tape() {
+        let dir = std::env::temp_dir().join(format!("zx-spectrum-tape-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sources = Sources::default();
+        let mut repl = Repl::new(&sources);
+        repl.state.tape = Some(Tape::new(&dir));
+        for line in ["10 LET b=a+1", "20 LOAD \"\"", "LET a=1", "SAVE \"first\" LINE 10"] {
+            repl.enter(line).unwrap();
+        }
+        for line in ["NEW", "10 LET c=5", "SAVE \"second\"", "NEW"] {
+            repl.enter(line).unwrap();
+        }
+        // The first program runs, then chain-loads the next one on the tape
+        repl.enter("LOAD \"first\"").unwrap();
+        assert_eq!(repl.program[&10], Instr::parse("LET c=5").unwrap().1);
+        assert_eq!(var(&repl, "b"), None);
+        repl.enter("VERIFY \"second\"").unwrap();
+        repl.enter("MERGE \"first\"").unwrap();
+        assert_eq!(repl.program.keys().copied().collect::<Vec<_>>(), vec![10, 20]);
+        assert_eq!(var(&repl, "a"), Some(1));
+        assert!(repl.enter("VERIFY \"second\"").is_err());
+
+        let err = repl.enter("LOAD \"missing\"").unwrap_err();
+        assert!(err.to_string().starts_with("R Tape loading error, 0:1"), "{}", err);
+        let err = repl.enter("SAVE \"\"").unwrap_err();
+        assert!(err.to_string().starts_with("F Invalid file name, 0:1"), "{}", err);
+        // Variables from tape count towards the limits
+        repl.state.limits.variables = Some(0);
+        let err = repl.enter("LOAD \"first\"").unwrap_err();
+        assert!(err.to_string().starts_with("X Limit exceeded, 0:1"), "{}", err);
+        repl.state.limits.variables = None;
+        repl.enter("SAVE \"screen\" SCREEN$").unwrap();
+        repl.enter("LOAD \"screen\" CODE 0").unwrap();
+        assert!(repl.enter("LOAD \"screen\" CODE 0,10").is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,73 @@
+//! Draws the Spectrum's screen from memory: the display file at 16384 holds a bit for each
+//! pixel, and the attributes at 22528 the colours of each 8x8 character square.
+pub const WIDTH: usize = 256;
+pub const HEIGHT: usize = 192;
+
+const DISPLAY_FILE: usize = 0x4000;
+const ATTRIBUTES: usize = 0x5800;
+
+/// Black, blue, red, magenta, green, cyan, yellow and white, then their BRIGHT versions.
+const PALETTE: [[u8; 3]; 16] = [
+    [0x00, 0x00, 0x00],
+    [0x00, 0x00, 0xD7],
+    [0xD7, 0x00, 0x00],
+    [0xD7, 0x00, 0xD7],
+    [0x00, 0xD7, 0x00],
+    [0x00, 0xD7, 0xD7],
+    [0xD7, 0xD7, 0x00],
+    [0xD7, 0xD7, 0xD7],
+    [0x00, 0x00, 0x00],
+    [0x00, 0x00, 0xFF],
+    [0xFF, 0x00, 0x00],
+    [0xFF, 0x00, 0xFF],
+    [0x00, 0xFF, 0x00],
+    [0x00, 0xFF, 0xFF],
+    [0xFF, 0xFF, 0x00],
+    [0xFF, 0xFF, 0xFF],
+];
+
+/// The address of the byte holding the 8 pixels from `(x, y)`. The thirds of the screen are
+/// stored one after another, but within a third the rows are interleaved.
+fn pixel_address(x: usize, y: usize) -> usize {
+    DISPLAY_FILE | ((y & 0xC0) << 5) | ((y & 0x07) << 8) | ((y & 0x38) << 2) | (x >> 3)
+}
+
+/// Renders the screen as RGB pixels, row by row. FLASH is drawn as if it hasn't flashed.
+pub fn render(memory: &[u8]) -> Vec<u8> {
+    let mut pixels = Vec::with_capacity(WIDTH * HEIGHT * 3);
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let attribute = memory[ATTRIBUTES + (y / 8) * 32 + x / 8];
+            let bright = ((attribute >> 3) & 0x08) as usize;
+            let ink = (attribute & 0x07) as usize | bright;
+            let paper = ((attribute >> 3) & 0x07) as usize | bright;
+            let set = memory[pixel_address(x, y)] & (0x80 >> (x & 7)) != 0;
+            pixels.extend(PALETTE[if set { ink } else { paper }]);
+        }
+    }
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, WIDTH};
+
+    #[test]
+    fn test_render() {
+        let mut memory = vec![0; 0x10000];
+        memory[0x4000] = 0b1000_0001; // The first row of the top-left square
+        memory[0x4100] = 0xFF; // Its second row
+        memory[0x4020] = 0xFF; // The first row of the square below it
+        memory[0x5800] = 0b0100_1010; // BRIGHT, blue paper, red ink
+        let pixels = render(&memory);
+        let pixel = |x: usize, y: usize| &pixels[(y * WIDTH + x) * 3..][..3];
+        assert_eq!(pixel(0, 0), [0xFF, 0x00, 0x00]);
+        assert_eq!(pixel(1, 0), [0x00, 0x00, 0xFF]);
+        assert_eq!(pixel(7, 0), [0xFF, 0x00, 0x00]);
+        assert_eq!(pixel(3, 1), [0xFF, 0x00, 0x00]);
+        assert_eq!(pixel(8, 0), [0x00, 0x00, 0x00]);
+        // Below, the attribute is black on black
+        assert_eq!(pixel(0, 8), [0x00, 0x00, 0x00]);
+        assert_eq!(pixels.len(), 256 * 192 * 3);
+    }
+}
@@ -0,0 +1,76 @@
+//! Reads the Spectrum's memory from `.sna` and `.z80` snapshots, and finds the BASIC program
+//! in it through the system variables, as the ROM does.
+mod sna;
+mod snapshot_tests;
+mod z80;
+
+use std::path::Path;
+
+use anyhow::{bail, ensure, Context, Result};
+
+use crate::tokenizer::{decode_program, decode_variables};
+
+/// Where the variables area starts, which is where the program ends.
+const VARS: usize = 0x5C4B;
+const PROG: usize = 0x5C53;
+/// Where the line being edited starts, just after the variables area.
+const E_LINE: usize = 0x5C59;
+/// The line and statement that CONTINUE goes on from.
+const OLDPPC: usize = 0x5C6E;
+const OSPPC: usize = 0x5C70;
+
+/// The address space of a snapshotted Spectrum.
+#[derive(Clone)]
+pub struct Snapshot {
+    pub memory: Box<[u8]>,
+}
+
+/// The BASIC program in a snapshot, with its numeric variables.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SnapshotProgram {
+    /// Numbered lines, ready for `parse_file` with `prefixed` set.
+    pub listing: String,
+    pub variables: Vec<(String, i64)>,
+    /// The line and (1-based) statement that CONTINUE would go on from.
+    pub cont: Option<(usize, usize)>,
+}
+
+impl Snapshot {
+    /// Reads a snapshot, as `.sna` or `.z80` by its extension.
+    pub fn read(path: &Path) -> Result<Snapshot> {
+        let bytes = std::fs::read(path).context("Failed to read file.")?;
+        let extension = path.extension().unwrap_or_default().to_ascii_lowercase();
+        match extension.to_str() {
+            Some("sna") => sna::parse(&bytes),
+            Some("z80") => z80::parse(&bytes),
+            _ => bail!("{} is not a .sna or .z80 snapshot", path.display()),
+        }
+        .context("Failed to read snapshot.")
+    }
+
+    pub fn is_snapshot(path: &Path) -> bool {
+        path.extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("sna") || ext.eq_ignore_ascii_case("z80"))
+    }
+
+    fn word(&self, address: usize) -> usize {
+        u16::from_le_bytes([self.memory[address], self.memory[address + 1]]) as usize
+    }
+
+    pub fn program(&self) -> Result<SnapshotProgram> {
+        let (prog, vars, e_line) = (self.word(PROG), self.word(VARS), self.word(E_LINE));
+        ensure!(
+            0x5C00 < prog && prog <= vars && vars < e_line,
+            "The system variables don't point to a program (PROG={}, VARS={}, E_LINE={})",
+            prog,
+            vars,
+            e_line
+        );
+        let (line, statement) = (self.word(OLDPPC), self.memory[OSPPC] as usize);
+        Ok(SnapshotProgram {
+            listing: decode_program(&self.memory[prog..vars])?,
+            variables: decode_variables(&self.memory[vars..e_line])?,
+            cont: (line <= 9999 && statement > 0).then_some((line, statement)),
+        })
+    }
+}
@@ -0,0 +1,23 @@
+use anyhow::{ensure, Result};
+
+use super::Snapshot;
+
+/// The registers, before the RAM.
+const HEADER_LEN: usize = 27;
+const RAM_LEN: usize = 0xC000;
+
+/// Reads a `.sna` snapshot: a header, then the 48K of RAM from 16384. 128K snapshots go on
+/// with the other RAM banks, but the first 48K is still what was paged in.
+pub fn parse(bytes: &[u8]) -> Result<Snapshot> {
+    ensure!(
+        bytes.len() >= HEADER_LEN + RAM_LEN,
+        "A .sna snapshot is at least {} bytes, not {}",
+        HEADER_LEN + RAM_LEN,
+        bytes.len()
+    );
+    let mut memory = vec![0; 0x10000];
+    memory[0x4000..].copy_from_slice(&bytes[HEADER_LEN..HEADER_LEN + RAM_LEN]);
+    Ok(Snapshot {
+        memory: memory.into_boxed_slice(),
+    })
+}
@@ -0,0 +1,140 @@
+#[cfg(test)]
+mod tests {
+    use crate::parser::parse_file;
+    use crate::snapshot::{sna, z80, SnapshotProgram, E_LINE, OLDPPC, OSPPC, PROG, VARS};
+    use crate::tokenizer::{encode_program, encode_variables};
+
+    const LISTING: &str = "10 LET total=0\n20 FOR i=1 TO 10: LET total=total+i: NEXT i\n30 PRINT total\n";
+
+    fn set_word(memory: &mut [u8], address: usize, value: usize) {
+        memory[address..address + 2].copy_from_slice(&(value as u16).to_le_bytes());
+    }
+
+    /// Memory holding `LISTING`, as the ROM lays it out, stopped at 20:2.
+    fn memory() -> Vec<u8> {
+        let lines = parse_file(LISTING, true).unwrap();
+        let program = encode_program(&lines).unwrap();
+        let variables = encode_variables([("total", 55), ("i", 11)]).unwrap();
+        let mut memory = vec![0; 0x10000];
+        let prog = 23755;
+        let vars = prog + program.len();
+        let e_line = vars + variables.len();
+        memory[prog..vars].copy_from_slice(&program);
+        memory[vars..e_line].copy_from_slice(&variables);
+        set_word(&mut memory, PROG, prog);
+        set_word(&mut memory, VARS, vars);
+        set_word(&mut memory, E_LINE, e_line);
+        set_word(&mut memory, OLDPPC, 20);
+        memory[OSPPC] = 2;
+        memory
+    }
+
+    fn expected() -> SnapshotProgram {
+        SnapshotProgram {
+            listing: LISTING.to_string(),
+            variables: vec![("total".to_string(), 55), ("i".to_string(), 11)],
+            cont: Some((20, 2)),
+        }
+    }
+
+    /// Compresses like the emulators do: runs of five or more, and of two or more EDs, become
+    /// `ED ED count byte`. The byte after a lone ED is never part of a run.
+    fn compress(data: &[u8]) -> Vec<u8> {
+        let mut out = vec![];
+        let mut i = 0;
+        while i < data.len() {
+            let run = data[i..].iter().take(255).take_while(|&&b| b == data[i]).count();
+            if run >= 5 || (data[i] == 0xED && run >= 2) {
+                out.extend([0xED, 0xED, run as u8, data[i]]);
+                i += run;
+            } else {
+                out.push(data[i]);
+                if data[i] == 0xED && i + 1 < data.len() {
+                    out.push(data[i + 1]);
+                    i += 1;
+                }
+                i += 1;
+            }
+        }
+        out
+    }
+
+    fn page(number: u8, data: &[u8], compressed: bool) -> Vec<u8> {
+        let data = if compressed { compress(data) } else { data.to_vec() };
+        let len: u16 = if compressed { data.len() as u16 } else { 0xFFFF };
+        [&len.to_le_bytes()[..], &[number], &data].concat()
+    }
+
+    fn header(extra_len: usize, mode: u8, port_7ffd: u8) -> Vec<u8> {
+        let mut header = vec![0; 32 + extra_len];
+        header[30..32].copy_from_slice(&(extra_len as u16).to_le_bytes());
+        header[34] = mode;
+        header[35] = port_7ffd;
+        header
+    }
+
+    #[test]
+    fn test_decompress() {
+        for data in [vec![0xED, 0, 0, 0, 0, 0, 0], vec![1, 0xED, 0xED, 2, 2, 2, 2, 2, 2, 0xED]] {
+            assert_eq!(z80::decompress(&compress(&data)).unwrap(), data);
+        }
+        assert!(z80::decompress(&[0xED, 0xED, 3]).is_err());
+    }
+
+    #[test]
+    fn test_sna() {
+        let memory = memory();
+        let bytes = [&[0; 27][..], &memory[0x4000..]].concat();
+        let snapshot = sna::parse(&bytes).unwrap();
+        assert_eq!(snapshot.program().unwrap(), expected());
+        assert!(sna::parse(&bytes[..1000]).is_err());
+        assert!(sna::parse(&[0; 27 + 0xC000]).unwrap().program().is_err());
+    }
+
+    #[test]
+    fn test_z80_v1() {
+        let memory = memory();
+        let mut header = vec![0; 30];
+        header[6] = 1; // PC
+        header[12] = 0x20; // Compressed
+        let bytes = [header, compress(&memory[0x4000..]), vec![0, 0xED, 0xED, 0]].concat();
+        assert_eq!(z80::parse(&bytes).unwrap().program().unwrap(), expected());
+    }
+
+    #[test]
+    fn test_z80_v3_48k() {
+        let memory = memory();
+        let bytes = [
+            header(54, 0, 0),
+            page(8, &memory[0x4000..0x8000], true),
+            page(4, &memory[0x8000..0xC000], false),
+            page(5, &memory[0xC000..], true),
+        ]
+        .concat();
+        assert_eq!(z80::parse(&bytes).unwrap().program().unwrap(), expected());
+    }
+
+    #[test]
+    fn test_z80_v2_128k() {
+        let memory = memory();
+        let bytes = [
+            header(23, 3, 0x10),
+            page(8, &memory[0x4000..0x8000], true),
+            page(5, &memory[0x8000..0xC000], true),
+            page(4, &[0xFF; 0x4000], true), // Bank 1, which isn't paged in
+            page(3, &memory[0xC000..], true),
+        ]
+        .concat();
+        assert_eq!(z80::parse(&bytes).unwrap().program().unwrap(), expected());
+        assert!(z80::parse(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_z80_truncated_header() {
+        let bytes = header(23, 3, 0x10);
+        for len in [31, 34, 35] {
+            let err = z80::parse(&bytes[..len]).err().unwrap();
+            assert_eq!(err.to_string(), "Truncated header");
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use anyhow::{anyhow, bail, ensure, Result};
+
+use super::Snapshot;
+
+const V1_HEADER_LEN: usize = 30;
+const PAGE_LEN: usize = 0x4000;
+/// Marks an uncompressed page in version 2 and 3 snapshots.
+const UNCOMPRESSED: u16 = 0xFFFF;
+
+/// Expands runs, which are stored as `ED ED count byte`.
+pub(super) fn decompress(data: &[u8]) -> Result<Vec<u8>> {
+    let mut out = vec![];
+    let mut i = 0;
+    while i < data.len() {
+        if data[i..].starts_with(&[0xED, 0xED]) {
+            let run = data.get(i + 2..i + 4).ok_or(anyhow!("Truncated run"))?;
+            out.extend(std::iter::repeat_n(run[1], run[0] as usize));
+            i += 4;
+        } else {
+            out.push(data[i]);
+            i += 1;
+        }
+    }
+    Ok(out)
+}
+
+/// Where a page is in memory, or `None` if it isn't paged in.
+fn page_address(page: u8, is_128k: bool, paged_bank: u8) -> Option<usize> {
+    match (is_128k, page) {
+        (false, 4) => Some(0x8000),
+        (false, 5) => Some(0xC000),
+        (false, 8) => Some(0x4000),
+        (true, 8) => Some(0x4000), // Bank 5
+        (true, 5) => Some(0x8000), // Bank 2
+        (true, page) if page == paged_bank + 3 => Some(0xC000),
+        _ => None,
+    }
+}
+
+/// Reads a `.z80` snapshot. Version 1 is a header then all 48K, maybe compressed. Versions 2
+/// and 3 have a longer header, then 16K pages, each with its length and page number.
+pub fn parse(bytes: &[u8]) -> Result<Snapshot> {
+    ensure!(bytes.len() > V1_HEADER_LEN, "A .z80 snapshot is more than {} bytes", V1_HEADER_LEN);
+    let mut memory = vec![0; 0x10000];
+    let pc = u16::from_le_bytes([bytes[6], bytes[7]]);
+    if pc != 0 {
+        let data = &bytes[V1_HEADER_LEN..];
+        // Flags byte 255 is read as 1, for compatibility with old snapshots
+        let compressed = bytes[12] != 0xFF && bytes[12] & 0x20 != 0;
+        let ram = match compressed {
+            true => decompress(data.strip_suffix(&[0, 0xED, 0xED, 0]).unwrap_or(data))?,
+            false => data.to_vec(),
+        };
+        ensure!(ram.len() == 0xC000, "Expected 48K of RAM, found {} bytes", ram.len());
+        memory[0x4000..].copy_from_slice(&ram);
+        return Ok(Snapshot {
+            memory: memory.into_boxed_slice(),
+        });
+    }
+
+    let extra_len = bytes.get(30..32).ok_or(anyhow!("Truncated header"))?;
+    let extra_len = u16::from_le_bytes([extra_len[0], extra_len[1]]) as usize;
+    let mode = *bytes.get(34).ok_or(anyhow!("Truncated header"))?;
+    let is_128k = match extra_len {
+        23 => mode >= 3,
+        54 | 55 => mode >= 4,
+        _ => bail!("Unknown .z80 version, with a {} byte header", extra_len),
+    };
+    let paged_bank = bytes.get(35).ok_or(anyhow!("Truncated header"))? & 0x07;
+    let mut rest = bytes.get(32 + extra_len..).ok_or(anyhow!("Truncated header"))?;
+    while !rest.is_empty() {
+        ensure!(rest.len() >= 3, "Truncated page header");
+        let len = u16::from_le_bytes([rest[0], rest[1]]);
+        let page = rest[2];
+        let stored_len = if len == UNCOMPRESSED { PAGE_LEN } else { len as usize };
+        let data = rest.get(3..3 + stored_len).ok_or(anyhow!("Truncated page {}", page))?;
+        let data = match len {
+            UNCOMPRESSED => data.to_vec(),
+            _ => decompress(data)?,
+        };
+        ensure!(data.len() == PAGE_LEN, "Page {} is {} bytes, not 16K", page, data.len());
+        if let Some(address) = page_address(page, is_128k, paged_bank) {
+            memory[address..address + PAGE_LEN].copy_from_slice(&data);
+        }
+        rest = &rest[3 + stored_len..];
+    }
+    Ok(Snapshot {
+        memory: memory.into_boxed_slice(),
+    })
+}
@@ -0,0 +1,14 @@
+use bumpalo::Bump;
+
+/// Keeps source text that parsed lines borrow from, for a whole session: lines typed into the
+/// REPL, breakpoint conditions, and programs LOADed from tape. It's all freed together when
+/// the session ends.
+#[derive(Debug, Default)]
+pub struct Sources(Bump);
+
+impl Sources {
+    /// Keeps a copy of `source`, which lasts as long as this does.
+    pub fn keep(&self, source: &str) -> &str {
+        self.0.alloc_str(source)
+    }
+}
@@ -0,0 +1,83 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+
+use super::tap::{read_files, write_files, FileType, TapeFile};
+
+/// The tape that SAVE writes to and LOAD reads from: either a directory, with a .tap image
+/// for each file, or a single .tap image that saved files are added to the end of.
+#[derive(Debug, Clone)]
+pub struct Tape {
+    path: PathBuf,
+    /// How many files along the tape LOAD has read, so `LOAD ""` gets the next one.
+    position: usize,
+}
+
+fn is_tap(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("tap"))
+}
+
+impl Tape {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Tape {
+            path: path.into(),
+            position: 0,
+        }
+    }
+
+    /// Every file on the tape, in order. A directory's images are read in order of their names.
+    pub fn files(&self) -> Result<Vec<TapeFile>> {
+        if !self.path.is_dir() {
+            return match self.path.exists() {
+                true => read_files(&fs::read(&self.path).context("Failed to read tape")?),
+                false => Ok(vec![]),
+            };
+        }
+        let mut paths = vec![];
+        for entry in fs::read_dir(&self.path).context("Failed to read tape directory")? {
+            paths.push(entry?.path());
+        }
+        paths.retain(|path| is_tap(path));
+        paths.sort();
+        let mut files = vec![];
+        for path in paths {
+            let bytes = fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+            files.extend(read_files(&bytes).with_context(|| format!("In {}", path.display()))?);
+        }
+        Ok(files)
+    }
+
+    pub fn save(&mut self, file: TapeFile) -> Result<()> {
+        let bytes = write_files(std::slice::from_ref(&file));
+        if self.path.is_dir() {
+            let path = self.path.join(format!("{}.tap", file.header.name));
+            return fs::write(&path, bytes).with_context(|| format!("Failed to write {}", path.display()));
+        }
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut tape| tape.write_all(&bytes))
+            .with_context(|| format!("Failed to write {}", self.path.display()))
+    }
+
+    /// Finds the next file called `name` (or the next file at all, if `name` is empty) of a
+    /// type that `wanted` accepts. Searching past the end of the tape rewinds it.
+    pub fn find(&mut self, name: &str, wanted: impl Fn(FileType) -> bool) -> Result<TapeFile> {
+        let mut files = self.files()?;
+        let found = (self.position..files.len()).chain(0..self.position).find(|&i| {
+            let header = &files[i].header;
+            wanted(header.file_type) && (name.is_empty() || header.name == name)
+        });
+        match found {
+            Some(i) => {
+                self.position = i + 1;
+                Ok(files.swap_remove(i))
+            }
+            None if name.is_empty() => Err(anyhow!("No more files on the tape")),
+            None => Err(anyhow!("No file called {:?} on the tape", name)),
+        }
+    }
+}
@@ -0,0 +1,8 @@
+mod deck;
+mod program;
+mod tap;
+mod tape_tests;
+
+pub use deck::Tape;
+pub use program::{load_program, program_file, save_program, TapeProgram};
+pub use tap::{FileType, TapeFile};
@@ -0,0 +1,65 @@
+use anyhow::{anyhow, ensure, Result};
+
+use super::tap::{read_files, write_files, FileType, TapeFile};
+use crate::parser::Line;
+use crate::tokenizer::{decode_program, decode_variables, encode_program, encode_variables};
+
+const NO_AUTOSTART: u16 = 0x8000;
+
+/// A BASIC program as saved to tape.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TapeProgram {
+    pub name: String,
+    pub autostart: Option<usize>,
+    /// Numbered lines, ready for `parse_file` with `prefixed` set.
+    pub listing: String,
+    pub variables: Vec<(String, i64)>,
+}
+
+/// A program, and its variables, as a tape file.
+pub fn program_file<'a>(
+    name: &str,
+    lines: &[Line],
+    autostart: Option<usize>,
+    variables: impl IntoIterator<Item = (&'a str, i64)>,
+) -> Result<TapeFile> {
+    let program = encode_program(lines)?;
+    let mut data = program.clone();
+    data.extend(encode_variables(variables)?);
+    let autostart = autostart.map_or(NO_AUTOSTART, |line| line as u16);
+    Ok(TapeFile::new(FileType::Program, name, data, autostart, program.len() as u16))
+}
+
+impl TapeProgram {
+    pub fn from_file(file: &TapeFile) -> Result<TapeProgram> {
+        let header = &file.header;
+        ensure!(header.file_type == FileType::Program, "{} is not a program", header.name);
+        let program_len = (header.param2 as usize).min(file.data.len());
+        Ok(TapeProgram {
+            name: header.name.clone(),
+            autostart: (header.param1 < NO_AUTOSTART).then_some(header.param1 as usize),
+            listing: decode_program(&file.data[..program_len])?,
+            variables: decode_variables(&file.data[program_len..])?,
+        })
+    }
+}
+
+/// Saves a program, and its variables, as a .tap image.
+pub fn save_program<'a>(
+    name: &str,
+    lines: &[Line],
+    autostart: Option<usize>,
+    variables: impl IntoIterator<Item = (&'a str, i64)>,
+) -> Result<Vec<u8>> {
+    Ok(write_files(&[program_file(name, lines, autostart, variables)?]))
+}
+
+/// Loads the first program in a .tap image.
+pub fn load_program(bytes: &[u8]) -> Result<TapeProgram> {
+    let files = read_files(bytes)?;
+    let file = files
+        .iter()
+        .find(|file| file.header.file_type == FileType::Program)
+        .ok_or(anyhow!("No program found on tape"))?;
+    TapeProgram::from_file(file)
+}
@@ -0,0 +1,159 @@
+use anyhow::{anyhow, ensure, Result};
+
+pub const HEADER_FLAG: u8 = 0x00;
+pub const DATA_FLAG: u8 = 0xFF;
+
+/// One block of a .tap file. On disk it is prefixed with its length, and followed by
+/// a checksum byte, the XOR of the flag and data bytes.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Block {
+    pub flag: u8,
+    pub data: Vec<u8>,
+}
+
+fn checksum(flag: u8, data: &[u8]) -> u8 {
+    data.iter().fold(flag, |acc, byte| acc ^ byte)
+}
+
+pub fn read_blocks(mut bytes: &[u8]) -> Result<Vec<Block>> {
+    let mut blocks = vec![];
+    while !bytes.is_empty() {
+        ensure!(bytes.len() >= 2, "Truncated block length");
+        let len = u16::from_le_bytes([bytes[0], bytes[1]]) as usize;
+        ensure!(len >= 2, "Block too short: {} bytes", len);
+        let block = bytes
+            .get(2..2 + len)
+            .ok_or(anyhow!("Truncated block: expected {} bytes", len))?;
+        let (flag, data, check) = (block[0], &block[1..len - 1], block[len - 1]);
+        ensure!(
+            checksum(flag, data) == check,
+            "Bad checksum in block {}",
+            blocks.len()
+        );
+        blocks.push(Block {
+            flag,
+            data: data.to_vec(),
+        });
+        bytes = &bytes[2 + len..];
+    }
+    Ok(blocks)
+}
+
+pub fn write_blocks(blocks: &[Block]) -> Vec<u8> {
+    let mut bytes = vec![];
+    for block in blocks {
+        bytes.extend((block.data.len() as u16 + 2).to_le_bytes());
+        bytes.push(block.flag);
+        bytes.extend(&block.data);
+        bytes.push(checksum(block.flag, &block.data));
+    }
+    bytes
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum FileType {
+    Program,
+    NumberArray,
+    CharArray,
+    Code,
+}
+
+/// The 17 bytes of a header block, describing the data block that follows it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Header {
+    pub file_type: FileType,
+    pub name: String,
+    pub length: u16,
+    /// For programs, the auto-start line (32768 or more for none). For code, the start address.
+    pub param1: u16,
+    /// For programs, the length of the program without its variables.
+    pub param2: u16,
+}
+
+impl Header {
+    pub fn parse(data: &[u8]) -> Result<Header> {
+        ensure!(data.len() == 17, "Header is {} bytes, not 17", data.len());
+        let file_type = match data[0] {
+            0 => FileType::Program,
+            1 => FileType::NumberArray,
+            2 => FileType::CharArray,
+            3 => FileType::Code,
+            other => return Err(anyhow!("Unknown file type {}", other)),
+        };
+        let word = |i: usize| u16::from_le_bytes([data[i], data[i + 1]]);
+        Ok(Header {
+            file_type,
+            name: data[1..11].iter().map(|&b| b as char).collect::<String>().trim_end().to_string(),
+            length: word(11),
+            param1: word(13),
+            param2: word(15),
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![self.file_type as u8];
+        // Names are exactly 10 characters, padded with spaces
+        bytes.extend(self.name.bytes().chain(std::iter::repeat(b' ')).take(10));
+        bytes.extend(self.length.to_le_bytes());
+        bytes.extend(self.param1.to_le_bytes());
+        bytes.extend(self.param2.to_le_bytes());
+        bytes
+    }
+}
+
+/// A header and the data block it describes, which is how the Spectrum saves everything.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TapeFile {
+    pub header: Header,
+    pub data: Vec<u8>,
+}
+
+impl TapeFile {
+    pub fn new(file_type: FileType, name: &str, data: Vec<u8>, param1: u16, param2: u16) -> Self {
+        let header = Header {
+            file_type,
+            name: name.to_string(),
+            length: data.len() as u16,
+            param1,
+            param2,
+        };
+        TapeFile { header, data }
+    }
+
+    pub fn blocks(&self) -> [Block; 2] {
+        [
+            Block {
+                flag: HEADER_FLAG,
+                data: self.header.to_bytes(),
+            },
+            Block {
+                flag: DATA_FLAG,
+                data: self.data.clone(),
+            },
+        ]
+    }
+}
+
+/// Reads the files in a .tap image, skipping any blocks without a header.
+pub fn read_files(bytes: &[u8]) -> Result<Vec<TapeFile>> {
+    let blocks = read_blocks(bytes)?;
+    let mut files = vec![];
+    let mut i = 0;
+    while i + 1 < blocks.len() {
+        if blocks[i].flag != HEADER_FLAG || blocks[i + 1].flag != DATA_FLAG {
+            i += 1;
+            continue;
+        }
+        files.push(TapeFile {
+            header: Header::parse(&blocks[i].data)?,
+            data: blocks[i + 1].data.clone(),
+        });
+        i += 2;
+    }
+    Ok(files)
+}
+
+pub fn write_files(files: &[TapeFile]) -> Vec<u8> {
+    let blocks: Vec<Block> = files.iter().flat_map(TapeFile::blocks).collect();
+    write_blocks(&blocks)
+}
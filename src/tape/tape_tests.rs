@@ -0,0 +1,71 @@
+#[cfg(test)]
+mod tests {
+    use crate::parser::parse_file;
+    use crate::tape::tap::{read_blocks, write_blocks, Block, FileType, Header};
+    use crate::tape::{load_program, save_program, Tape, TapeFile};
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn test_blocks() {
+        let header = Header {
+            file_type: FileType::Code,
+            name: "screen".to_string(),
+            length: 6912,
+            param1: 16384,
+            param2: 32768,
+        };
+        assert_eq!(Header::parse(&header.to_bytes()).unwrap(), header);
+        let blocks = vec![
+            Block { flag: 0, data: header.to_bytes() },
+            Block { flag: 0xFF, data: vec![1, 2, 3] },
+        ];
+        let mut bytes = write_blocks(&blocks);
+        assert_eq!(read_blocks(&bytes).unwrap(), blocks);
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        assert!(read_blocks(&bytes).is_err());
+        assert!(read_blocks(&bytes[..last]).is_err());
+    }
+
+    #[test]
+    fn test_programs_round_trip() {
+        for (dir, prefixed) in [("basic", false), ("intermediate", true)] {
+            for entry in fs::read_dir(Path::new("programs").join(dir)).expect("Directory not found") {
+                let path = entry.expect("Failed to read entry").path();
+                if path.extension().is_none_or(|ext| ext != "zx") {
+                    continue;
+                }
+                let content = fs::read_to_string(&path).expect("Failed to read file");
+                let lines = parse_file(&content, prefixed).expect("Failed to parse file");
+                let tape = save_program("test", &lines, Some(10), [("x", 42)]).unwrap();
+                let program = load_program(&tape).unwrap();
+                assert_eq!(program.name, "test");
+                assert_eq!(program.autostart, Some(10));
+                assert_eq!(program.variables, vec![("x".to_string(), 42)]);
+                assert_eq!(parse_file(&program.listing, true), Ok(lines), "{:?}", path);
+            }
+        }
+    }
+
+    #[test]
+    fn test_tape_file() {
+        let path = std::env::temp_dir().join(format!("zx-spectrum-{}.tap", std::process::id()));
+        let mut tape = Tape::new(&path);
+        for name in ["one", "two", "one"] {
+            let file = TapeFile::new(FileType::Code, name, name.bytes().collect(), 0, 32768);
+            tape.save(file).unwrap();
+        }
+        let names: Vec<_> = tape.files().unwrap().into_iter().map(|f| f.header.name).collect();
+        assert_eq!(names, ["one", "two", "one"]);
+        let code = |_| true;
+        assert_eq!(tape.find("two", code).unwrap().header.name, "two");
+        // The search carries on from the last file found, rewinding at the end
+        assert_eq!(tape.find("", code).unwrap().data, b"one");
+        assert_eq!(tape.find("", code).unwrap().data, b"one");
+        assert_eq!(tape.find("", code).unwrap().data, b"two");
+        assert!(tape.find("three", code).is_err());
+        assert!(tape.find("", |file_type| file_type == FileType::Program).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
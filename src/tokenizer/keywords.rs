@@ -0,0 +1,53 @@
+/// The first keyword token. The codes below it are characters, block graphics and UDGs.
+pub const FIRST_TOKEN: u8 = 0xA3;
+
+pub const RND: u8 = 0xA5;
+pub const PI: u8 = 0xA7;
+pub const LESS_EQUAL: u8 = 0xC7;
+pub const NOT_EQUAL: u8 = 0xC9;
+pub const REM: u8 = 0xEA;
+
+/// The keywords for every token from `FIRST_TOKEN` (SPECTRUM and PLAY are 128K only).
+const KEYWORDS: [&str; 93] = [
+    "SPECTRUM", "PLAY", "RND", "INKEY$", "PI", "FN", "POINT", "SCREEN$", "ATTR", "AT", "TAB",
+    "VAL$", "CODE", "VAL", "LEN", "SIN", "COS", "TAN", "ASN", "ACS", "ATN", "LN", "EXP", "INT",
+    "SQR", "SGN", "ABS", "PEEK", "IN", "USR", "STR$", "CHR$", "NOT", "BIN", "OR", "AND", "<=",
+    ">=", "<>", "LINE", "THEN", "TO", "STEP", "DEF FN", "CAT", "FORMAT", "MOVE", "ERASE",
+    "OPEN #", "CLOSE #", "MERGE", "VERIFY", "BEEP", "CIRCLE", "INK", "PAPER", "FLASH", "BRIGHT",
+    "INVERSE", "OVER", "OUT", "LPRINT", "LLIST", "STOP", "READ", "DATA", "RESTORE", "NEW",
+    "BORDER", "CONTINUE", "DIM", "REM", "FOR", "GO TO", "GO SUB", "INPUT", "LOAD", "LIST", "LET",
+    "PAUSE", "NEXT", "POKE", "PRINT", "PLOT", "RUN", "SAVE", "RANDOMIZE", "IF", "CLS", "DRAW",
+    "CLEAR", "RETURN", "COPY",
+];
+
+pub fn keyword(token: u8) -> Option<&'static str> {
+    token.checked_sub(FIRST_TOKEN).map(|i| KEYWORDS[i as usize])
+}
+
+/// How many bytes of `text` match `keyword`, ignoring case and the spaces inside it
+/// (so `GOTO` is `GO TO`). A keyword ending in a letter can't run on into a name.
+fn match_len(text: &str, keyword: &str) -> Option<usize> {
+    let mut len = 0;
+    for (i, part) in keyword.split(' ').enumerate() {
+        if i > 0 {
+            len += text[len..].len() - text[len..].trim_start_matches(' ').len();
+        }
+        let rest = text[len..].get(..part.len())?;
+        if !rest.eq_ignore_ascii_case(part) {
+            return None;
+        }
+        len += part.len();
+    }
+    let runs_on = text[len..].starts_with(|c: char| c.is_ascii_alphanumeric());
+    match keyword.ends_with(|c: char| c.is_ascii_alphabetic()) && runs_on {
+        true => None,
+        false => Some(len),
+    }
+}
+
+/// Finds the longest keyword at the start of `text`, returning its token and length.
+pub fn find_keyword(text: &str) -> Option<(u8, usize)> {
+    (FIRST_TOKEN..=0xFF)
+        .filter_map(|token| Some((token, match_len(text, keyword(token)?)?)))
+        .max_by_key(|&(_, len)| len)
+}
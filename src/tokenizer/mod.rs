@@ -0,0 +1,211 @@
+//! Converts between listings and the Spectrum's in-memory program format, where each line is
+//! its number (big-endian), length (little-endian) and text, ending with `0x0D`. In the text,
+//! keywords are single-byte tokens, and each number is followed by `0x0E` and its 5-byte form.
+//!
+//! Bytes with no printable character, such as the colour control codes, are written in
+//! listings as `\{n}`, e.g. `PRINT "\{16}\{2}red"` for INK 2.
+mod keywords;
+mod number;
+mod tokenizer_tests;
+mod variables;
+
+use anyhow::{anyhow, bail, ensure, Result};
+
+use crate::parser::Line;
+pub(crate) use keywords::find_keyword;
+use keywords::{keyword, FIRST_TOKEN, LESS_EQUAL, NOT_EQUAL, PI, REM, RND};
+pub use number::encode_number;
+pub use variables::{decode_variables, encode_variables};
+
+/// Marks the hidden 5-byte form of a number, which follows its digits.
+pub const NUMBER_MARKER: u8 = 0x0E;
+pub const END_OF_LINE: u8 = 0x0D;
+
+const BIN: u8 = 0xC4;
+/// INK, PAPER, FLASH, BRIGHT, INVERSE and OVER take one parameter byte; AT and TAB take two.
+const CONTROL_CODES: std::ops::RangeInclusive<u8> = 0x10..=0x17;
+const POUND: u8 = 0x60;
+const COPYRIGHT: u8 = 0x7F;
+
+fn control_params(code: u8) -> usize {
+    match code {
+        0x16 | 0x17 => 2,
+        _ => 1,
+    }
+}
+
+/// Reads a `\{n}` escape at the start of `text`, returning the byte and the escape's length.
+fn escape(text: &str) -> Option<(u8, usize)> {
+    let digits = text.strip_prefix("\\{")?;
+    let end = digits.find('}')?;
+    Some((digits[..end].parse().ok()?, end + 3))
+}
+
+fn char_byte(c: char) -> Result<u8> {
+    match c {
+        '£' => Ok(POUND),
+        '©' => Ok(COPYRIGHT),
+        ' '..='~' if c != '`' => Ok(c as u8),
+        _ => Err(anyhow!("{:?} is not in the Spectrum's character set", c)),
+    }
+}
+
+/// The length of the number at the start of `text`, with its value.
+fn number(text: &str, binary: bool) -> Option<(usize, f64)> {
+    if binary {
+        let len = text.find(|c| c != '0' && c != '1').unwrap_or(text.len());
+        return Some((len, u64::from_str_radix(&text[..len], 2).ok()? as f64));
+    }
+    let mut len = text.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(text.len());
+    let exponent = text[len..].strip_prefix(['e', 'E']).map(|e| e.strip_prefix(['+', '-']).unwrap_or(e));
+    if let Some(digits) = exponent.filter(|e| e.starts_with(|c: char| c.is_ascii_digit())) {
+        let sign = text.len() - len - digits.len();
+        len += sign + digits.find(|c: char| !c.is_ascii_digit()).unwrap_or(digits.len());
+    }
+    Some((len, text[..len].parse().ok()?))
+}
+
+/// Tokenizes the text of one line (without its line number).
+pub fn tokenize(text: &str) -> Result<Vec<u8>> {
+    let mut bytes: Vec<u8> = vec![];
+    let mut rest = text;
+    let mut in_string = false;
+    // Keywords and numbers can't start in the middle of a variable name
+    let mut after_ident = false;
+    let mut after_bin = false;
+    while let Some(c) = rest.chars().next() {
+        if let Some((byte, len)) = escape(rest) {
+            bytes.push(byte);
+            rest = &rest[len..];
+            continue;
+        }
+        if !in_string && !after_ident {
+            if let Some((token, len)) = find_keyword(rest) {
+                // The spaces around keywords are added when listing, rather than stored
+                if bytes.last() == Some(&b' ') {
+                    bytes.pop();
+                }
+                bytes.push(token);
+                rest = &rest[len..];
+                rest = rest.strip_prefix(' ').unwrap_or(rest);
+                if token == REM {
+                    for c in rest.chars() {
+                        bytes.push(char_byte(c)?);
+                    }
+                    break;
+                }
+                after_bin = token == BIN;
+                continue;
+            }
+            if let Some((len, value)) = (c.is_ascii_digit() || c == '.')
+                .then(|| number(rest, after_bin))
+                .flatten()
+            {
+                bytes.extend(rest[..len].bytes());
+                bytes.push(NUMBER_MARKER);
+                bytes.extend(encode_number(value)?);
+                rest = &rest[len..];
+                after_bin = false;
+                continue;
+            }
+        }
+        in_string ^= c == '"';
+        after_ident = !in_string && c.is_ascii_alphanumeric();
+        after_bin &= c == ' ';
+        bytes.push(char_byte(c)?);
+        rest = &rest[c.len_utf8()..];
+    }
+    Ok(bytes)
+}
+
+/// Turns the tokenized text of one line back into text, as LIST shows it.
+pub fn detokenize(bytes: &[u8]) -> String {
+    let mut text = String::new();
+    // The space after a keyword, left off at the end of the line
+    let mut space = false;
+    let mut i = 0;
+    while i < bytes.len() {
+        let byte = bytes[i];
+        i += 1;
+        if std::mem::take(&mut space) {
+            text.push(' ');
+        }
+        match byte {
+            NUMBER_MARKER => i += 5,
+            // So that the listing doesn't read as an escape
+            b'\\' if bytes.get(i) == Some(&b'{') => text.push_str("\\{92}"),
+            POUND => text.push('£'),
+            COPYRIGHT => text.push('©'),
+            b' '..=b'~' => text.push(byte as char),
+            _ if CONTROL_CODES.contains(&byte) => {
+                // The parameters are raw bytes, even if they look like a number marker
+                let end = (i + control_params(byte)).min(bytes.len());
+                for &b in [byte].iter().chain(&bytes[i..end]) {
+                    text.push_str(&format!("\\{{{}}}", b));
+                }
+                i = end;
+            }
+            FIRST_TOKEN.. => {
+                let keyword = keyword(byte).expect("every byte from FIRST_TOKEN is a keyword");
+                match byte {
+                    LESS_EQUAL..=NOT_EQUAL => text.push_str(keyword),
+                    RND..=PI => text.push_str(keyword),
+                    // Functions follow an operator, so only get a space after them
+                    _ if byte <= BIN => {
+                        text.push_str(keyword);
+                        space = true;
+                    }
+                    _ => {
+                        if !text.is_empty() && !text.ends_with(' ') {
+                            text.push(' ');
+                        }
+                        text.push_str(keyword);
+                        space = true;
+                    }
+                }
+            }
+            _ => text.push_str(&format!("\\{{{}}}", byte)),
+        }
+    }
+    text
+}
+
+/// Encodes one numbered line.
+pub fn encode_line(number: usize, text: &str) -> Result<Vec<u8>> {
+    ensure!(number <= 9999, "Line number {} is more than 9999", number);
+    let mut body = tokenize(text)?;
+    body.push(END_OF_LINE);
+    let mut bytes = (number as u16).to_be_bytes().to_vec();
+    bytes.extend((body.len() as u16).to_le_bytes());
+    bytes.extend(body);
+    Ok(bytes)
+}
+
+/// Encodes a program for the Spectrum's program area.
+pub fn encode_program(lines: &[Line]) -> Result<Vec<u8>> {
+    let mut bytes = vec![];
+    for line in lines {
+        bytes.extend(encode_line(line.number, &line.instr.to_string())?);
+    }
+    Ok(bytes)
+}
+
+/// Decodes the program area into a numbered listing.
+pub fn decode_program(mut bytes: &[u8]) -> Result<String> {
+    let mut listing = String::new();
+    while !bytes.is_empty() {
+        ensure!(bytes.len() >= 4, "Truncated line header");
+        let number = u16::from_be_bytes([bytes[0], bytes[1]]);
+        if number > 0x3FFF {
+            break; // The Spectrum stops listing at line numbers it can't hold
+        }
+        let len = u16::from_le_bytes([bytes[2], bytes[3]]) as usize;
+        let Some(text) = bytes.get(4..4 + len) else {
+            bail!("Truncated line {}", number);
+        };
+        let text = text.strip_suffix(&[END_OF_LINE]).unwrap_or(text);
+        listing.push_str(&format!("{} {}\n", number, detokenize(text)));
+        bytes = &bytes[4 + len..];
+    }
+    Ok(listing)
+}
@@ -0,0 +1,47 @@
+use anyhow::{ensure, Result};
+
+/// Encodes a number in the Spectrum's 5-byte format: whole numbers up to 65535 are stored
+/// as "small integers", anything else as an 8-bit exponent and 32-bit mantissa.
+pub fn encode_number(n: f64) -> Result<[u8; 5]> {
+    if n.fract() == 0.0 && n.abs() <= 65535.0 {
+        let [lo, hi, ..] = (n as i32).to_le_bytes();
+        return Ok([0, if n < 0.0 { 0xFF } else { 0 }, lo, hi, 0]);
+    }
+    ensure!(n.is_finite(), "Number too big: {}", n);
+    // The mantissa is the magnitude scaled into [0.5, 1), as a 32-bit fraction
+    let magnitude = n.abs();
+    let mut exponent = magnitude.log2().floor() as i32 + 1;
+    while magnitude / 2f64.powi(exponent) >= 1.0 {
+        exponent += 1;
+    }
+    while magnitude / 2f64.powi(exponent) < 0.5 {
+        exponent -= 1;
+    }
+    let mut mantissa = (magnitude / 2f64.powi(exponent - 32)).round() as u64;
+    if mantissa >> 32 != 0 {
+        mantissa >>= 1;
+        exponent += 1;
+    }
+    ensure!(exponent < 128, "Number too big: {}", n);
+    if exponent <= -128 {
+        return Ok([0; 5]); // Too small, so rounds to zero
+    }
+    // The top bit is always set, so it holds the sign instead
+    let mantissa = (mantissa as u32 & 0x7FFF_FFFF) | if n < 0.0 { 0x8000_0000 } else { 0 };
+    let [a, b, c, d] = mantissa.to_be_bytes();
+    Ok([(exponent + 128) as u8, a, b, c, d])
+}
+
+pub fn decode_number(bytes: &[u8]) -> f64 {
+    if bytes[0] == 0 {
+        let value = u16::from_le_bytes([bytes[2], bytes[3]]) as f64;
+        return if bytes[1] == 0xFF { value - 65536.0 } else { value };
+    }
+    let mantissa = u32::from_be_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]);
+    let magnitude = (mantissa | 0x8000_0000) as f64 * 2f64.powi(bytes[0] as i32 - 128 - 32);
+    if mantissa >> 31 == 1 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
@@ -0,0 +1,84 @@
+#[cfg(test)]
+mod tests {
+    use crate::parser::parse_file;
+    use crate::tokenizer::number::decode_number;
+    use crate::tokenizer::{
+        decode_program, decode_variables, detokenize, encode_line, encode_number, encode_program,
+        encode_variables, tokenize,
+    };
+
+    #[test]
+    fn test_numbers() {
+        assert_eq!(encode_number(10.0).unwrap(), [0, 0, 10, 0, 0]);
+        assert_eq!(encode_number(-1.0).unwrap(), [0, 0xFF, 0xFF, 0xFF, 0]);
+        assert_eq!(encode_number(65536.0).unwrap(), [0x91, 0, 0, 0, 0]);
+        assert_eq!(encode_number(0.5).unwrap(), [0x80, 0, 0, 0, 0]);
+        assert_eq!(encode_number(-0.5).unwrap(), [0x80, 0x80, 0, 0, 0]);
+        assert_eq!(encode_number(0.1).unwrap(), [0x7D, 0x4C, 0xCC, 0xCC, 0xCD]);
+        for n in [0.0, 1.0, -1.0, 65535.0, -65535.0, 65536.0, -1e5, 2147483648.0, 0.25, -1.5] {
+            assert_eq!(decode_number(&encode_number(n).unwrap()), n, "{}", n);
+        }
+        assert!(encode_number(1e39).is_err());
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let bytes = tokenize("GO TO 10").unwrap();
+        assert_eq!(bytes, [&[0xEC, b'1', b'0', 0x0E][..], &encode_number(10.0).unwrap()].concat());
+        assert_eq!(tokenize("goto 10").unwrap(), bytes);
+        for text in [
+            "GO TO 10",
+            "IF a<=b THEN PRINT \"to go\": STOP",
+            "LET total2=x1+2",
+            "FOR i=1 TO 10 STEP 2",
+            "REM GO TO 10 :-)",
+            "PRINT",
+            "LET a=INT (RND*6)+1",
+            "PRINT AT 0,0; INK 2;\"£5\"",
+            "IF a AND NOT b THEN DEF FN f(x)=x*PI",
+            "LET x=1.5e-3+BIN 101",
+        ] {
+            assert_eq!(detokenize(&tokenize(text).unwrap()), text);
+        }
+        // Keywords aren't found inside names or strings
+        assert_eq!(tokenize("LET tops=1").unwrap()[..5], [0xF1, b't', b'o', b'p', b's']);
+        assert!(!tokenize("PRINT \"STOP\"").unwrap().contains(&0xE2));
+        // The longest keyword wins
+        assert_eq!(tokenize("INPUT a").unwrap()[0], 0xEE);
+        assert_eq!(tokenize("PRINT INKEY$").unwrap(), [0xF5, 0xA6]);
+        assert_eq!(tokenize("PRINT BIN 11").unwrap()[5..], encode_number(3.0).unwrap());
+        assert!(tokenize("PRINT \"€\"").is_err());
+    }
+
+    #[test]
+    fn test_control_codes() {
+        let text = "PRINT \"\\{16}\\{2}red\\{22}\\{14}\\{3}\"";
+        let bytes = tokenize(text).unwrap();
+        assert_eq!(bytes, b"\xF5\"\x10\x02red\x16\x0E\x03\"");
+        assert_eq!(detokenize(&bytes), text);
+        assert_eq!(detokenize(b"\x90\\{"), "\\{144}\\{92}{");
+    }
+
+    #[test]
+    fn test_program() {
+        assert_eq!(
+            encode_line(10, "CLS").unwrap(),
+            [0x00, 0x0A, 0x02, 0x00, 0xFB, 0x0D]
+        );
+        assert!(encode_line(10000, "CLS").is_err());
+        let source = "10 LET a=5\n20 IF a>=5 THEN PRINT \"big\": GO SUB 100\n100 RETURN\n";
+        let lines = parse_file(source, true).unwrap();
+        let bytes = encode_program(&lines).unwrap();
+        assert_eq!(decode_program(&bytes).unwrap(), source);
+        assert!(decode_program(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_variables() {
+        let variables = vec![("a".to_string(), 1), ("total".to_string(), -70000)];
+        let bytes = encode_variables(variables.iter().map(|(n, v)| (n.as_str(), *v))).unwrap();
+        assert_eq!(bytes[0], 0x61);
+        assert_eq!(*bytes.last().unwrap(), 0x80);
+        assert_eq!(decode_variables(&bytes).unwrap(), variables);
+    }
+}
@@ -0,0 +1,60 @@
+use anyhow::{anyhow, Result};
+
+use super::number::{decode_number, encode_number};
+
+const END_OF_VARIABLES: u8 = 0x80;
+
+/// Encodes numeric variables for the variables area, which follows the program.
+pub fn encode_variables<'a>(variables: impl IntoIterator<Item = (&'a str, i64)>) -> Result<Vec<u8>> {
+    let mut bytes = vec![];
+    for (name, value) in variables {
+        let name = name.to_ascii_lowercase().replace(' ', "");
+        let name = name.as_bytes();
+        if name.len() == 1 {
+            bytes.push(0x60 | (name[0] & 0x1F));
+        } else {
+            bytes.push(0xA0 | (name[0] & 0x1F));
+            bytes.extend(&name[1..name.len() - 1]);
+            bytes.push(name[name.len() - 1] | 0x80);
+        }
+        bytes.extend(encode_number(value as f64)?);
+    }
+    bytes.push(END_OF_VARIABLES);
+    Ok(bytes)
+}
+
+/// Decodes the numeric variables from the variables area. String and array variables are
+/// skipped, and FOR loop variables only keep their current value.
+pub fn decode_variables(mut bytes: &[u8]) -> Result<Vec<(String, i64)>> {
+    let mut variables = vec![];
+    let letter = |byte: u8| ((byte & 0x1F) | 0x60) as char;
+    let too_short = || anyhow!("Truncated variables area");
+    while let Some(&first) = bytes.first() {
+        let (name, rest) = match first >> 5 {
+            _ if first == END_OF_VARIABLES => break,
+            0b011 | 0b111 => (letter(first).to_string(), &bytes[1..]),
+            0b101 => {
+                let end = bytes[1..].iter().position(|b| b & 0x80 != 0);
+                let end = end.ok_or_else(too_short)? + 1;
+                let mut name = letter(first).to_string();
+                name.extend(bytes[1..end].iter().map(|&b| b as char));
+                name.push((bytes[end] & 0x7F) as char);
+                (name, &bytes[end + 1..])
+            }
+            // Strings and arrays, with a 2-byte length
+            0b010 | 0b100 | 0b110 => {
+                let len = bytes.get(1..3).ok_or_else(too_short)?;
+                let len = u16::from_le_bytes([len[0], len[1]]) as usize;
+                bytes = bytes.get(3 + len..).ok_or_else(too_short)?;
+                continue;
+            }
+            _ => return Err(anyhow!("Unknown variable type: {:#04x}", first)),
+        };
+        let value = rest.get(..5).ok_or_else(too_short)?;
+        variables.push((name, decode_number(value).round() as i64));
+        // FOR loop variables also have their limit, step, and looping line and statement
+        let len = if first >> 5 == 0b111 { 18 } else { 5 };
+        bytes = rest.get(len..).ok_or_else(too_short)?;
+    }
+    Ok(variables)
+}
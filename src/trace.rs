@@ -0,0 +1,204 @@
+//! Logs what a program does as it runs: each statement, and each variable it sets.
+use std::fmt::{Debug, Formatter};
+use std::io::Write;
+
+use crate::parser::Instr;
+
+/// Told about everything a program does, as it does it.
+pub trait Tracer: Debug {
+    /// A statement is about to run, at `line:statement` (1-based).
+    fn statement(&mut self, line: usize, statement: usize, instr: &Instr);
+    /// The statement that's running set a variable, by LET, INPUT, FOR or NEXT.
+    fn assign(&mut self, name: &str, value: i64);
+    /// The same, for a string variable, by LET or INPUT.
+    fn assign_string(&mut self, name: &str, value: &str);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// `10:1 LET a=1` for a statement, then `  a = 1` for what it set.
+    Human,
+    /// One JSON object a line, with the position in each, e.g.
+    /// `{"line":10,"statement":1,"source":"LET a=1"}` then
+    /// `{"line":10,"statement":1,"var":"a","value":1}`.
+    Json,
+}
+
+/// Writes the trace to a file or stream. Errors writing it are ignored, so a trace can't stop
+/// the program.
+pub struct TraceWriter<W: Write> {
+    out: W,
+    format: TraceFormat,
+    position: (usize, usize),
+}
+
+impl<W: Write> TraceWriter<W> {
+    pub fn new(out: W, format: TraceFormat) -> Self {
+        TraceWriter {
+            out,
+            format,
+            position: (0, 0),
+        }
+    }
+}
+
+impl<W: Write> Debug for TraceWriter<W> {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "TraceWriter({:?})", self.format)
+    }
+}
+
+/// Quotes a string for JSON.
+fn json_string(s: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+impl<W: Write> Tracer for TraceWriter<W> {
+    fn statement(&mut self, line: usize, statement: usize, instr: &Instr) {
+        self.position = (line, statement);
+        let _ = match self.format {
+            TraceFormat::Human => writeln!(self.out, "{}:{} {}", line, statement, instr),
+            TraceFormat::Json => writeln!(
+                self.out,
+                r#"{{"line":{},"statement":{},"source":{}}}"#,
+                line,
+                statement,
+                json_string(&instr.to_string())
+            ),
+        };
+    }
+
+    fn assign(&mut self, name: &str, value: i64) {
+        let (line, statement) = self.position;
+        let _ = match self.format {
+            TraceFormat::Human => writeln!(self.out, "  {} = {}", name, value),
+            TraceFormat::Json => writeln!(
+                self.out,
+                r#"{{"line":{},"statement":{},"var":{},"value":{}}}"#,
+                line,
+                statement,
+                json_string(name),
+                value
+            ),
+        };
+    }
+
+    fn assign_string(&mut self, name: &str, value: &str) {
+        let (line, statement) = self.position;
+        let _ = match self.format {
+            TraceFormat::Human => writeln!(self.out, "  {} = {}", name, json_string(value)),
+            TraceFormat::Json => writeln!(
+                self.out,
+                r#"{{"line":{},"statement":{},"var":{},"value":{}}}"#,
+                line,
+                statement,
+                json_string(name),
+                json_string(value)
+            ),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::io::Write;
+    use std::rc::Rc;
+
+    use super::{json_string, TraceFormat, TraceWriter};
+    use crate::console::Scripted;
+    use crate::exec::{ErrorCode, Limits};
+    use crate::parser::parse_file;
+    use crate::{Interpreter, Status};
+
+    /// A buffer that stays readable after the tracer writing to it is given away.
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn trace_until(format: TraceFormat, limits: Limits) -> (Status, String) {
+        let program = "10 INPUT a: PRINT \"a\";a\n20 FOR i=1 TO 2\n30 NEXT i";
+        let mut interpreter = Interpreter::new(parse_file(program, true).unwrap());
+        let out = Shared::default();
+        interpreter.set_console(Box::new(Scripted::new(["5"])));
+        interpreter.set_tracer(Box::new(TraceWriter::new(out.clone(), format)));
+        interpreter.set_limits(limits);
+        let status = interpreter.run();
+        let trace = String::from_utf8(out.0.borrow().clone()).unwrap();
+        (status, trace)
+    }
+
+    fn trace(format: TraceFormat) -> String {
+        let (status, trace) = trace_until(format, Limits::default());
+        assert_eq!(status, Status::Finished);
+        trace
+    }
+
+    #[test]
+    fn test_human() {
+        let expected = "\
+10:1 INPUT a
+  a = 5
+10:2 PRINT \"a\";a
+20:1 FOR i=1 TO 2
+  i = 1
+30:1 NEXT i
+  i = 2
+30:1 NEXT i
+  i = 3
+";
+        assert_eq!(trace(TraceFormat::Human), expected);
+    }
+
+    #[test]
+    fn test_json() {
+        let trace = trace(TraceFormat::Json);
+        let lines: Vec<_> = trace.lines().collect();
+        assert_eq!(lines.len(), 9);
+        assert_eq!(lines[0], r#"{"line":10,"statement":1,"source":"INPUT a"}"#);
+        assert_eq!(lines[1], r#"{"line":10,"statement":1,"var":"a","value":5}"#);
+        assert_eq!(lines[2], r#"{"line":10,"statement":2,"source":"PRINT \"a\";a"}"#);
+        assert_eq!(lines[8], r#"{"line":30,"statement":1,"var":"i","value":3}"#);
+        assert_eq!(json_string("\\\n\x07"), r#""\\\n\u0007""#);
+    }
+
+    #[test]
+    fn test_limit() {
+        // The statement that goes over the limit doesn't run, so isn't traced
+        let limits = Limits {
+            statements: Some(3),
+            ..Limits::default()
+        };
+        let (status, trace) = trace_until(TraceFormat::Human, limits);
+        assert!(matches!(status, Status::Stopped(report) if report.code == ErrorCode::LimitExceeded));
+        assert_eq!(trace, "10:1 INPUT a\n  a = 5\n10:2 PRINT \"a\";a\n20:1 FOR i=1 TO 2\n  i = 1\n");
+        // Nor is an assignment the limits turn down
+        let limits = Limits {
+            variables: Some(1),
+            ..Limits::default()
+        };
+        let (status, trace) = trace_until(TraceFormat::Human, limits);
+        assert!(matches!(status, Status::Stopped(report) if report.code == ErrorCode::LimitExceeded));
+        assert_eq!(trace, "10:1 INPUT a\n  a = 5\n10:2 PRINT \"a\";a\n20:1 FOR i=1 TO 2\n");
+    }
+}